@@ -23,8 +23,7 @@ fn generate_ascii_lines(num_lines: usize, line_length: usize) -> String {
         .take(line_length)
         .collect();
 
-    std::iter::repeat(line)
-        .take(num_lines)
+    std::iter::repeat_n(line, num_lines)
         .collect::<Vec<_>>()
         .join("\n")
 }
@@ -36,8 +35,7 @@ fn generate_unicode_lines(num_lines: usize, line_length: usize) -> String {
         .take(line_length)
         .collect();
 
-    std::iter::repeat(line)
-        .take(num_lines)
+    std::iter::repeat_n(line, num_lines)
         .collect::<Vec<_>>()
         .join("\n")
 }
@@ -70,7 +68,7 @@ fn bench_process_truecolor(c: &mut Criterion) {
 
         group.throughput(Throughput::Bytes(size as u64));
         group.bench_with_input(
-            BenchmarkId::from_parameter(format!("{}lines_{}chars", num_lines, line_len)),
+            BenchmarkId::from_parameter(format!("{num_lines}lines_{line_len}chars")),
             &input,
             |b, input| {
                 b.iter(|| {
@@ -86,7 +84,7 @@ fn bench_process_truecolor(c: &mut Criterion) {
                         ColorMode::TrueColor,
                     );
 
-                    result.unwrap()
+                    result.unwrap();
                 });
             },
         );
@@ -104,7 +102,7 @@ fn bench_process_256color(c: &mut Criterion) {
 
         group.throughput(Throughput::Bytes(size as u64));
         group.bench_with_input(
-            BenchmarkId::from_parameter(format!("{}lines_{}chars", num_lines, line_len)),
+            BenchmarkId::from_parameter(format!("{num_lines}lines_{line_len}chars")),
             &input,
             |b, input| {
                 b.iter(|| {
@@ -120,7 +118,7 @@ fn bench_process_256color(c: &mut Criterion) {
                         ColorMode::Color256,
                     );
 
-                    result.unwrap()
+                    result.unwrap();
                 });
             },
         );
@@ -149,7 +147,7 @@ fn bench_process_unicode(c: &mut Criterion) {
                 ColorMode::TrueColor,
             );
 
-            result.unwrap()
+            result.unwrap();
         });
     });
 
@@ -177,7 +175,7 @@ fn bench_process_mixed(c: &mut Criterion) {
                 ColorMode::TrueColor,
             );
 
-            result.unwrap()
+            result.unwrap();
         });
     });
 
@@ -194,7 +192,7 @@ fn bench_process_mixed(c: &mut Criterion) {
                 ColorMode::Color256,
             );
 
-            result.unwrap()
+            result.unwrap();
         });
     });
 
@@ -222,7 +220,7 @@ fn bench_process_slow_change(c: &mut Criterion) {
                 ColorMode::TrueColor,
             );
 
-            result.unwrap()
+            result.unwrap();
         });
     });
 
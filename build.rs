@@ -3,62 +3,7 @@ use std::fs::File;
 use std::io::{BufWriter, Write};
 use std::path::Path;
 
-const AMPLITUDE: f64 = 127.0;
-const OFFSET: f64 = 128.0;
-const C: f64 = -0.5; // cos(2π/3)
-const S: f64 = 0.866_025_403_784_438_6_f64; // sin(2π/3) = √3/2
-const TABLE_SIZE: usize = 2048;
-
-#[derive(Clone, Copy)]
-struct Color(u8, u8, u8);
-
-/// Branch-free saturating f64 to u8 converter
-fn fast_f64_to_u8_sat(x: f64) -> u8 {
-    let y = x.clamp(0.0, 255.0) + 0.5;
-    y as u8
-}
-
-/// Convert RGB to 256-color palette (duplicated from color.rs for build script)
-const fn rgb_to_256(r: u8, g: u8, b: u8) -> u8 {
-    if r == g && g == b {
-        if r < 8 {
-            16
-        } else if r > 248 {
-            231
-        } else {
-            232 + (((((r as u16) - 8) * 25) >> 8) as u8)
-        }
-    } else {
-        // Approximate division by 51 with bit shift
-        let r6 = ((r as u16) * 5) >> 8;
-        let g6 = ((g as u16) * 5) >> 8;
-        let b6 = ((b as u16) * 5) >> 8;
-        (16 + 36 * r6 + 6 * g6 + b6) as u8
-    }
-}
-
-/// Build frequency-agnostic rainbow color table using trig recurrence
-fn build_table() -> [Color; TABLE_SIZE] {
-    let mut arr = [Color(0, 0, 0); TABLE_SIZE];
-
-    let delta = std::f64::consts::TAU / (TABLE_SIZE as f64);
-    let (mut sx, mut cx) = (0.0_f64).sin_cos();
-    let (sd, cd) = delta.sin_cos();
-
-    for color in &mut arr {
-        let r = fast_f64_to_u8_sat(sx.mul_add(AMPLITUDE, OFFSET));
-        let g = fast_f64_to_u8_sat((sx * C + cx * S).mul_add(AMPLITUDE, OFFSET));
-        let b = fast_f64_to_u8_sat((sx * C - cx * S).mul_add(AMPLITUDE, OFFSET));
-        *color = Color(r, g, b);
-
-        let ns = sx.mul_add(cd, cx * sd);
-        let nc = cx.mul_add(cd, -sx * sd);
-        sx = ns;
-        cx = nc;
-    }
-
-    arr
-}
+use lolcat_ultra_table::{TABLE_SIZE, build_table, rgb_to_256};
 
 /// Format a byte sequence as a Rust byte array literal
 fn format_byte_array(bytes: &[u8]) -> String {
@@ -186,7 +131,7 @@ fn main() {
         } else if value > 248 {
             231
         } else {
-            232 + ((((value as u16 - 8) * 25) >> 8) as u8)
+            232 + ((((u16::from(value) - 8) * 25) >> 8) as u8)
         };
         writeln!(color_file, "    {code},").unwrap();
     }
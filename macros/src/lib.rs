@@ -0,0 +1,308 @@
+//! Compile-time rainbow string literals for lolcat-ultra.
+//!
+//! `rainbow!("text")` expands to a `&'static str` containing `text` with
+//! truecolor escape sequences, and `rainbow_256!("text")` to the same with
+//! 256-color sequences. Both follow the same per-line phase as
+//! `lolcat_ultra::RainbowWriter` and end with the terminal reset sequence.
+//!
+//! Optional arguments override the defaults used by the `lolcat-ultra` CLI:
+//!
+//! ```ignore
+//! const BANNER: &str = rainbow!("lolcat-ultra", frequency = 0.1, spread = 3.0, offset = 12.0);
+//! ```
+
+use std::fmt::Write;
+use std::sync::OnceLock;
+
+use proc_macro::TokenStream;
+use syn::parse::{Parse, ParseStream};
+use syn::{Ident, LitStr, Token, parse_macro_input};
+
+use lolcat_ultra_table::{Color, MASK, TABLE_SIZE, build_table, rgb_to_256};
+
+/// Same as lolcat-ultra's `MAX_ANSI_SEQUENCE_LENGTH`
+const MAX_ANSI_SEQUENCE_LENGTH: usize = 200;
+
+/// Same as lolcat-ultra's default `TabStops::Every(8)`
+const TAB_SIZE: usize = 8;
+
+const RESET: &str = "\x1b[0m\x1b[39m\x1b[49m";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Palette {
+    TrueColor,
+    Color256,
+}
+
+struct Input {
+    text: LitStr,
+    frequency: f64,
+    spread: f64,
+    offset: f64,
+}
+
+/// Parse a float argument, accepting integer literals too
+fn parse_number(input: ParseStream) -> syn::Result<f64> {
+    let negative = input.peek(Token![-]);
+    if negative {
+        input.parse::<Token![-]>()?;
+    }
+    let lookahead = input.lookahead1();
+    let value = if lookahead.peek(syn::LitFloat) {
+        input.parse::<syn::LitFloat>()?.base10_parse::<f64>()?
+    } else if lookahead.peek(syn::LitInt) {
+        input.parse::<syn::LitInt>()?.base10_parse::<f64>()?
+    } else {
+        return Err(lookahead.error());
+    };
+    Ok(if negative { -value } else { value })
+}
+
+impl Parse for Input {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let text: LitStr = input.parse()?;
+        let mut parsed = Self {
+            text,
+            frequency: 0.04,
+            spread: 4.0,
+            offset: 0.0,
+        };
+
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            let name: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            let value = parse_number(input)?;
+            match name.to_string().as_str() {
+                "frequency" => {
+                    if !value.is_finite() || value <= 0.0 {
+                        return Err(syn::Error::new(
+                            name.span(),
+                            format!("invalid frequency: {value}"),
+                        ));
+                    }
+                    parsed.frequency = value;
+                }
+                "spread" => {
+                    if !value.is_finite() || value <= 0.0 {
+                        return Err(syn::Error::new(
+                            name.span(),
+                            format!("invalid spread: {value}"),
+                        ));
+                    }
+                    parsed.spread = value;
+                }
+                "offset" => parsed.offset = value,
+                other => {
+                    return Err(syn::Error::new(
+                        name.span(),
+                        format!(
+                            "unknown argument `{other}`: expected `frequency`, `spread` or `offset`"
+                        ),
+                    ));
+                }
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
+/// Colors text exactly like `RainbowWriter` followed by `finish()`
+struct Colorizer {
+    table: &'static [Color; TABLE_SIZE],
+    palette: Palette,
+    scale: f64,
+    spread: f64,
+    offset: f64,
+    out: String,
+    lines_read: usize,
+    phase: u64,
+    phase_inc: u64,
+    last_color_idx: Option<usize>,
+    column: usize,
+}
+
+impl Colorizer {
+    fn new(input: &Input, palette: Palette) -> Self {
+        static TABLE: OnceLock<[Color; TABLE_SIZE]> = OnceLock::new();
+
+        let scale = (TABLE_SIZE as f64) * (input.frequency / std::f64::consts::TAU);
+        let mut colorizer = Self {
+            table: TABLE.get_or_init(build_table),
+            palette,
+            scale,
+            spread: input.spread,
+            offset: input.offset,
+            out: String::new(),
+            lines_read: 0,
+            phase: 0,
+            phase_inc: 0,
+            last_color_idx: None,
+            column: 0,
+        };
+        colorizer.start_line();
+        colorizer
+    }
+
+    /// Same fixed-point math as `RainbowLookup::fixedpoint_phase`
+    fn start_line(&mut self) {
+        let s = self.scale * ((1u64 << 32) as f64);
+        let start_pos = (self.lines_read as f64) * self.spread + self.offset;
+        self.phase = (start_pos * s) as u64;
+        self.phase_inc = (1.0 / self.spread * s) as u64;
+        self.last_color_idx = None;
+        self.column = 0;
+    }
+
+    fn push_colored(&mut self, c: char) {
+        let idx = ((self.phase >> 32) as usize) & MASK;
+        if self.last_color_idx != Some(idx) {
+            let Color(r, g, b) = self.table[idx];
+            // Writing to a `String` cannot fail
+            let _ = match self.palette {
+                Palette::TrueColor => write!(self.out, "\x1b[38;2;{r:03};{g:03};{b:03}m"),
+                Palette::Color256 => write!(self.out, "\x1b[38;5;{}m", rgb_to_256(r, g, b)),
+            };
+            self.last_color_idx = Some(idx);
+        }
+        self.out.push(c);
+        self.phase = self.phase.wrapping_add(self.phase_inc);
+        self.column += 1;
+    }
+
+    fn colorize(mut self, text: &str) -> String {
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\r' if chars.peek() == Some(&'\n') => {}
+                '\n' => {
+                    self.out.push('\n');
+                    self.lines_read += 1;
+                    self.start_line();
+                }
+                '\x1b' => {
+                    self.out.push(c);
+                    let mut len = 0;
+                    while len < MAX_ANSI_SEQUENCE_LENGTH {
+                        match chars.peek() {
+                            Some(&next) if next != '\n' => {
+                                chars.next();
+                                self.out.push(next);
+                                len += next.len_utf8();
+                                if next.is_ascii_alphabetic() {
+                                    break;
+                                }
+                            }
+                            _ => break,
+                        }
+                    }
+                    self.last_color_idx = None;
+                }
+                '\t' => {
+                    for _ in 0..TAB_SIZE - self.column % TAB_SIZE {
+                        self.push_colored(' ');
+                    }
+                }
+                _ => self.push_colored(c),
+            }
+        }
+        self.out.push_str(RESET);
+        self.out
+    }
+}
+
+fn expand(input: TokenStream, palette: Palette) -> TokenStream {
+    let input = parse_macro_input!(input as Input);
+    let colored = Colorizer::new(&input, palette).colorize(&input.text.value());
+    let mut literal = proc_macro::Literal::string(&colored);
+    literal.set_span(input.text.span().unwrap());
+    TokenStream::from(proc_macro::TokenTree::Literal(literal))
+}
+
+/// Expand a string literal to a `&'static str` with truecolor rainbow escapes
+#[proc_macro]
+pub fn rainbow(input: TokenStream) -> TokenStream {
+    expand(input, Palette::TrueColor)
+}
+
+/// Expand a string literal to a `&'static str` with 256-color rainbow escapes
+#[proc_macro]
+pub fn rainbow_256(input: TokenStream) -> TokenStream {
+    expand(input, Palette::Color256)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn colorize(args: &str, palette: Palette) -> String {
+        let input: Input = syn::parse_str(args).unwrap();
+        Colorizer::new(&input, palette).colorize(&input.text.value())
+    }
+
+    /// `text` with every escape sequence taken out
+    fn strip(text: &str) -> String {
+        let mut plain = String::new();
+        let mut in_escape = false;
+        for c in text.chars() {
+            match c {
+                '\x1b' => in_escape = true,
+                c if in_escape => in_escape = !c.is_ascii_alphabetic(),
+                c => plain.push(c),
+            }
+        }
+        plain
+    }
+
+    #[test]
+    fn colors_every_line_and_resets() {
+        let colored = colorize(r#""ab\r\ncd""#, Palette::TrueColor);
+        let Color(r, g, b) = build_table()[0];
+        assert!(colored.starts_with(&format!("\x1b[38;2;{r:03};{g:03};{b:03}ma")));
+        assert!(colored.ends_with(RESET));
+        assert_eq!(strip(&colored), "ab\ncd");
+        // Each line starts with a color sequence of its own
+        assert!(colored.contains("\n\x1b[38;2;"));
+    }
+
+    #[test]
+    fn palettes_pick_the_same_colors() {
+        let truecolor = colorize(r#""x", offset = 12"#, Palette::TrueColor);
+        let color256 = colorize(r#""x", offset = 12"#, Palette::Color256);
+        let rgb: Vec<u8> = truecolor["\x1b[38;2;".len()..truecolor.find('m').unwrap()]
+            .split(';')
+            .map(|part| part.parse().unwrap())
+            .collect();
+        let code = rgb_to_256(rgb[0], rgb[1], rgb[2]);
+        assert!(color256.starts_with(&format!("\x1b[38;5;{code}mx")));
+    }
+
+    #[test]
+    fn tabs_expand_and_escapes_pass_through() {
+        let colored = colorize(r#""a\tb\x1b[1mc""#, Palette::TrueColor);
+        assert_eq!(strip(&colored), "a       bc");
+        assert!(colored.contains("\x1b[1m"));
+    }
+
+    #[test]
+    fn arguments_are_checked() {
+        for (args, message) in [
+            (r#""x", frequency = 0"#, "invalid frequency: 0"),
+            (r#""x", spread = -1"#, "invalid spread: -1"),
+            (
+                r#""x", speed = 1"#,
+                "unknown argument `speed`: expected `frequency`, `spread` or `offset`",
+            ),
+        ] {
+            let err = syn::parse_str::<Input>(args).err().unwrap();
+            assert_eq!(err.to_string(), message);
+        }
+        let input: Input = syn::parse_str(r#""x", frequency = 1, spread = 2.5,"#).unwrap();
+        assert!((input.frequency - 1.0).abs() < f64::EPSILON);
+        assert!((input.spread - 2.5).abs() < f64::EPSILON);
+    }
+}
@@ -1,25 +1,67 @@
-use anyhow::{Context, Result};
 use arrayvec::ArrayVec;
-use std::io::Write;
+use std::io::{self, Write};
 
 pub(crate) const MAX_ANSI_SEQUENCE_LENGTH: usize = 200;
 
 /// Byte-level ANSI escape processor. Reads an ANSI escape sequence starting
 /// at `bytes[pos]` (which should be 0x1B) and writes it directly to `writer`.
-/// Returns the new position after the escape sequence.
+/// Returns the new position after the escape sequence, and the number of
+/// bytes consumed after ESC if the sequence was cut off by the end of `bytes`.
 #[inline]
 pub(crate) fn process_ansi_escape_bytes<W: Write>(
     writer: &mut W,
     bytes: &[u8],
     pos: usize,
-) -> Result<usize> {
+) -> io::Result<(usize, Option<usize>)> {
+    continue_ansi_escape_bytes(writer, bytes, pos, None)
+}
+
+/// Continue an ANSI escape sequence that was cut off by the end of the
+/// previous segment, `count` bytes after its ESC. Returns the same as
+/// `process_ansi_escape_bytes`.
+#[inline]
+pub(crate) fn resume_ansi_escape_bytes<W: Write>(
+    writer: &mut W,
+    bytes: &[u8],
+    count: usize,
+) -> io::Result<(usize, Option<usize>)> {
+    continue_ansi_escape_bytes(writer, bytes, 0, Some(count))
+}
+
+/// Length of the escape sequence at the start of `bytes` if it moves the
+/// cursor to the first column (`ESC [ G` or `ESC [ 1 G`), as some progress
+/// bars do instead of writing `\r`
+pub(crate) fn line_start_escape_len(bytes: &[u8]) -> Option<usize> {
+    let params = bytes.strip_prefix(b"\x1b[")?;
+    let digits = params.iter().take_while(|b| b.is_ascii_digit()).count();
+    if params.get(digits) != Some(&b'G') {
+        return None;
+    }
+    let column = params[..digits].iter().try_fold(0u32, |column, &digit| {
+        column.checked_mul(10)?.checked_add(u32::from(digit - b'0'))
+    })?;
+    (column <= 1).then_some(2 + digits + 1)
+}
+
+fn continue_ansi_escape_bytes<W: Write>(
+    writer: &mut W,
+    bytes: &[u8],
+    pos: usize,
+    resume_count: Option<usize>,
+) -> io::Result<(usize, Option<usize>)> {
     let mut buf = ArrayVec::<u8, { MAX_ANSI_SEQUENCE_LENGTH + 4 }>::new();
 
-    // Push the ESC byte
-    buf.push(bytes[pos]);
-    let mut i = pos + 1;
-    let mut ansi_char_count = 0;
+    let mut i = pos;
+    let mut ansi_char_count = if let Some(count) = resume_count {
+        count
+    } else {
+        // Push the ESC byte
+        buf.push(bytes[pos]);
+        i += 1;
+        0
+    };
     let end = bytes.len();
+    let mut terminated = false;
 
     while i < end && ansi_char_count < MAX_ANSI_SEQUENCE_LENGTH {
         let b = bytes[i];
@@ -29,13 +71,13 @@ pub(crate) fn process_ansi_escape_bytes<W: Write>(
 
         // ANSI sequences end on ASCII alphabetic characters (A–Z, a–z)
         if b.is_ascii_alphabetic() {
+            terminated = true;
             break;
         }
     }
 
-    writer
-        .write_all(&buf)
-        .context("Failed to write ANSI escape sequence")?;
+    writer.write_all(&buf)?;
 
-    Ok(i)
+    let open = !terminated && ansi_char_count < MAX_ANSI_SEQUENCE_LENGTH;
+    Ok((i, open.then_some(ansi_char_count)))
 }
@@ -0,0 +1,323 @@
+//! Blocks of text buffered for the directions placed around the centre of
+//! the text, [`Direction::Radial`] and [`Direction::Conical`], and for
+//! fitting the rainbow to the text with [`Config::fit`].
+//!
+//! A block is read whole, measured for its width and height, and then
+//! colored like any other input, with each line's colors worked out from the
+//! block's centre or the frequency fitted to it. Blocks are bounded by
+//! [`Config::block_lines`] (or paragraphs, when fitting to them) and
+//! [`MAX_BLOCK_BYTES`], so long input is colored block by block.
+
+use std::f64::consts::TAU;
+use std::io::{self, BufRead};
+
+use crate::ansi::MAX_ANSI_SEQUENCE_LENGTH;
+use crate::config::{CarriageReturns, Config, Decorations, Direction, LineNumbers};
+
+/// Most bytes buffered for a block. A block ends early after the line that
+/// reaches this, and a longer line is cut here, carrying on with the
+/// centre of the block it started in.
+pub(crate) const MAX_BLOCK_BYTES: usize = 4 << 20;
+
+/// Width of a terminal cell relative to its height, so the rainbow comes
+/// out round rather than stretched along the lines
+const CELL_ASPECT: f64 = 0.5;
+
+/// How big a block is and where its centre is
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Block {
+    /// Input line number of the block's first line
+    first_line: usize,
+    /// Columns of the widest line
+    width: usize,
+    height: usize,
+    /// Centre in columns from the start of the lines
+    centre_x: f64,
+    /// Centre in lines from the first line
+    centre_y: f64,
+}
+
+/// A line of a block, to work out the rainbow position of its columns
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BlockLine {
+    centre_x: f64,
+    /// Distance down from the centre, in lines
+    dy: f64,
+}
+
+/// Whether a line starting `text` is empty, so it separates paragraphs
+fn starts_empty_line(text: &[u8]) -> bool {
+    text.starts_with(b"\n") || text.starts_with(b"\r\n")
+}
+
+/// Append up to `max_lines` lines from `reader` to `block`, stopping early
+/// at the end of the input or once the block holds [`MAX_BLOCK_BYTES`].
+/// With `paragraphs`, the block also ends where the lines switch between
+/// empty and not, so a paragraph and the empty lines around it are read as
+/// separate blocks.
+///
+/// Returns the length of the block. A line starting with a `\r` at the end
+/// of the reader's buffer is only known to be empty (`\r\n`) once more is
+/// read, so that `\r` may be left after the block in `block`, to start the
+/// next one: only the block itself should be drained before the next call.
+pub(crate) fn read_block<R: BufRead>(
+    reader: &mut R,
+    max_lines: usize,
+    paragraphs: bool,
+    block: &mut Vec<u8>,
+) -> io::Result<usize> {
+    let mut lines = 0;
+    // Whether the block is empty lines, once its first line is seen
+    let mut empty = None;
+    // Where the current line starts in `block`, until it is known whether
+    // it is empty
+    let mut line_start = paragraphs.then_some(0);
+    while lines < max_lines && block.len() < MAX_BLOCK_BYTES {
+        let available = reader.fill_buf()?;
+        if available.is_empty() {
+            break;
+        }
+        if let Some(start) = line_start {
+            let starts_empty = if block.len() > start {
+                // The line's `\r`, read ahead
+                available.starts_with(b"\n")
+            } else if available == b"\r" {
+                block.push(b'\r');
+                reader.consume(1);
+                continue;
+            } else {
+                starts_empty_line(available)
+            };
+            if *empty.get_or_insert(starts_empty) != starts_empty {
+                return Ok(start);
+            }
+            line_start = None;
+        }
+        let room = available.len().min(MAX_BLOCK_BYTES - block.len());
+        let end = match available[..room].iter().position(|&b| b == b'\n') {
+            Some(nl) => {
+                lines += 1;
+                nl + 1
+            }
+            None => room,
+        };
+        block.extend_from_slice(&available[..end]);
+        reader.consume(end);
+        if paragraphs && block.last() == Some(&b'\n') {
+            line_start = Some(block.len());
+        }
+    }
+    Ok(block.len())
+}
+
+impl Block {
+    /// Measure the lines of `text`, the first of which is input line
+    /// `first_line`
+    pub(crate) fn measure(text: &[u8], first_line: usize, config: &Config) -> Self {
+        let text = text.strip_suffix(b"\n").unwrap_or(text);
+        let mut width = 0;
+        let mut height: usize = 0;
+        for line in text.split(|&b| b == b'\n') {
+            width = width.max(line_width(line, config));
+            height += 1;
+        }
+        Self {
+            first_line,
+            width,
+            height,
+            centre_x: width.saturating_sub(1) as f64 / 2.0,
+            centre_y: (height - 1) as f64 / 2.0,
+        }
+    }
+
+    /// Input line `line`, which may be past the block if a line longer than
+    /// [`MAX_BLOCK_BYTES`] carries on after it
+    pub(crate) fn line(&self, line: usize) -> BlockLine {
+        BlockLine {
+            centre_x: self.centre_x,
+            dy: (line - self.first_line) as f64 - self.centre_y,
+        }
+    }
+
+    /// The frequency at which one full rainbow spans the block, or `None`
+    /// if the rainbow does not change across it
+    pub(crate) fn fitted_frequency(&self, config: &Config) -> Option<f64> {
+        let (width, height) = (self.width as f64, self.height as f64);
+        let span = match config.direction {
+            Direction::Radial => {
+                // From the centre out to the corners
+                (width / 2.0 * CELL_ASPECT).hypot(height / 2.0) * config.spread
+            }
+            Direction::Conical => TAU / config.frequency,
+            _ => width * config.column_step().abs() + height * config.line_step().abs(),
+        };
+        let frequency = TAU / span;
+        (frequency.is_finite() && frequency > 0.0).then_some(frequency)
+    }
+}
+
+impl BlockLine {
+    /// Rainbow position of `column`
+    #[inline]
+    pub(crate) fn position(self, column: usize, config: &Config) -> f64 {
+        let dx = (column as f64 - self.centre_x) * CELL_ASPECT;
+        let position = match config.direction {
+            Direction::Conical => self.dy.atan2(dx) / config.frequency,
+            _ => dx.hypot(self.dy) * config.spread,
+        };
+        position + config.offset()
+    }
+}
+
+/// Columns taken by `line` once written, with its decorations
+fn line_width(line: &[u8], config: &Config) -> usize {
+    let decorations = config.decorations;
+    let line = if decorations.show_nonprinting {
+        line
+    } else {
+        line.strip_suffix(b"\r").unwrap_or(line)
+    };
+    let mut column = if decorations.line_numbers == LineNumbers::Off {
+        0
+    } else {
+        // Six digits and a tab
+        6 + config.tab_stops.width_at(6)
+    };
+    let mut widest = 0;
+    let mut i = 0;
+    while i < line.len() {
+        let b = line[i];
+        i += 1;
+        if let Some(width) = shown_width(b, decorations) {
+            column += width;
+        } else if b == 0x1b {
+            let end = (i + MAX_ANSI_SEQUENCE_LENGTH).min(line.len());
+            i = line[i..end]
+                .iter()
+                .position(u8::is_ascii_alphabetic)
+                .map_or(end, |len| i + len + 1);
+        } else if b == b'\t' {
+            column += config.tab_stops.width_at(column);
+        } else if b == b'\r' && config.carriage_returns == CarriageReturns::Rewind {
+            widest = widest.max(column);
+            column = 0;
+        } else if !(0x80..0xC0).contains(&b) {
+            column += 1;
+        }
+    }
+    if decorations.show_ends {
+        column += 1;
+    }
+    widest.max(column)
+}
+
+/// Columns taken by `b` when the decorations show it as `^X` or `M-`
+fn shown_width(b: u8, decorations: Decorations) -> Option<usize> {
+    if b == b'\t' {
+        return decorations.show_tabs.then_some(2);
+    }
+    if !decorations.show_nonprinting {
+        return None;
+    }
+    let (prefix, b) = if b >= 0x80 { (2, b - 0x80) } else { (0, b) };
+    let width = match b {
+        0..0x20 | 0x7F => 2,
+        _ if prefix == 0 => return None,
+        _ => 1,
+    };
+    Some(prefix + width)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use super::*;
+    use crate::config::TabStops;
+
+    #[test]
+    fn measures_lines_as_written() {
+        let mut config = Config::default();
+        assert_eq!(line_width(b"caf\xc3\xa9\t\x1b[1mx\r", &config), 9);
+        config.tab_stops = TabStops::Every(4);
+        config.decorations.line_numbers = LineNumbers::All;
+        config.decorations.show_ends = true;
+        assert_eq!(line_width(b"a\tb", &config), 8 + 4 + 1 + 1);
+        config.decorations.show_nonprinting = true;
+        assert_eq!(
+            line_width(b"\x1b\xc3\xa9\r", &config),
+            8 + 2 + 3 + 3 + 2 + 1
+        );
+
+        let config = Config {
+            carriage_returns: CarriageReturns::Rewind,
+            ..Config::default()
+        };
+        assert_eq!(line_width(b"longer\rshort", &config), 6);
+    }
+
+    #[test]
+    fn blocks_are_bounded() {
+        let input = "one\ntwo\nthree\n";
+        let mut reader = input.as_bytes();
+        let mut block = Vec::new();
+        read_block(&mut reader, 2, false, &mut block).unwrap();
+        assert_eq!(block, b"one\ntwo\n");
+
+        let long = vec![b'x'; MAX_BLOCK_BYTES + 10];
+        let mut reader = long.as_slice();
+        block.clear();
+        read_block(&mut reader, 2, false, &mut block).unwrap();
+        assert_eq!(block.len(), MAX_BLOCK_BYTES);
+        assert_eq!(reader.len(), 10);
+
+        // Small buffers end right after a `\r`, before telling whether its
+        // line is empty
+        let input = b"one\r\ntwo\n\r\n\nthree\r\n\r\n";
+        for capacity in [1, 2, 3, 1024] {
+            let mut reader = BufReader::with_capacity(capacity, &input[..]);
+            let mut paragraphs = Vec::new();
+            block.clear();
+            loop {
+                let len = read_block(&mut reader, usize::MAX, true, &mut block).unwrap();
+                if len == 0 {
+                    break;
+                }
+                paragraphs.push(String::from_utf8(block[..len].to_vec()).unwrap());
+                block.drain(..len);
+            }
+            assert_eq!(
+                paragraphs,
+                ["one\r\ntwo\n", "\r\n\n", "three\r\n", "\r\n"],
+                "capacity {capacity}"
+            );
+        }
+
+        let block = Block::measure(b"ab\nabcde\n\n", 4, &Config::default());
+        assert!((block.centre_x - 2.0).abs() < f64::EPSILON);
+        assert!((block.line(4).dy + 1.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn fitted_frequency_spans_one_rainbow() {
+        let mut config = Config::try_new(0.1, 2.0, false).unwrap();
+        let block = Block::measure(b"abcd\nab\nabc\n", 0, &config);
+        let fitted = |config: &Config| block.fitted_frequency(config).unwrap();
+
+        // 4 columns half a spread apart, and 3 lines a spread apart
+        assert!((fitted(&config) - TAU / 8.0).abs() < 1e-12);
+        config.direction = Direction::Horizontal;
+        assert!((fitted(&config) - TAU / 2.0).abs() < 1e-12);
+        config.direction = Direction::Angle(180.0);
+        assert!((fitted(&config) - TAU / 2.0).abs() < 1e-12);
+        config.direction = Direction::Conical;
+        assert!((fitted(&config) - 0.1).abs() < 1e-12);
+
+        config.direction = Direction::Vertical;
+        let line = Block::measure(b"abcd\n", 0, &config);
+        assert_eq!(line.fitted_frequency(&config), Some(TAU / 2.0));
+        let empty = Block::measure(b"\n", 0, &config);
+        config.direction = Direction::Horizontal;
+        assert_eq!(empty.fitted_frequency(&config), None);
+    }
+}
@@ -0,0 +1,1418 @@
+//! Command-line parsing for the `lolcat-ultra` binary.
+//!
+//! Every option is described once, in an option table that drives both the
+//! parser and the `--help` text, so the two cannot drift apart. The parser
+//! follows the usual conventions: `--name=value` or `--name value`, bundled
+//! short flags with an optional value at the end (`-nEf 0.1`, `-f0.1`), and
+//! `--` to end the options.
+//!
+//! Settings in the config file are named like the long options and applied
+//! through the same table, before the command line.
+
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use lolcat_ultra::{
+    Buffering, CarriageReturns, ColorDepth, Config, ConfigError, Decorations, Direction, Fit,
+    Layer, LineEndings, LineNumbers, TabStops, Tabs,
+};
+
+use crate::config_file::{self, Setting, Value};
+use crate::generate::Shell;
+
+pub(crate) const ABOUT: &str = "cat with rainbow colors";
+
+/// Width the help text is wrapped to
+const HELP_WIDTH: usize = 80;
+
+/// Column where option descriptions start in the help text
+const HELP_INDENT: usize = 31;
+
+/// Something to do instead of coloring the input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Command {
+    Help,
+    Version,
+    /// Print a man page
+    Man,
+    /// Print a completion script. The shell follows `--generate
+    /// completions`, so it is `None` only while parsing.
+    Completions(Option<Shell>),
+}
+
+#[derive(Debug)]
+#[allow(clippy::struct_excessive_bools)]
+pub(crate) struct Args {
+    /// Set by `--help` or `--version`
+    pub(crate) command: Option<Command>,
+    /// Whether the options of the original lolcat were parsed
+    pub(crate) compat: bool,
+    /// Whether `-s` and `-v` are taken as `cat` takes them
+    pub(crate) cat: bool,
+    /// Config file profile to apply on top of its top-level settings
+    pub(crate) profile: Option<String>,
+    /// Whether to skip the config file
+    pub(crate) no_config: bool,
+    /// Input files, with `-` for stdin
+    pub(crate) inputs: Vec<PathBuf>,
+    /// Whether each input starts the rainbow over instead of continuing it
+    pub(crate) restart_per_file: bool,
+    pub(crate) frequency: f64,
+    pub(crate) spread: f64,
+    pub(crate) force: bool,
+    pub(crate) max_lines: Option<usize>,
+    /// Whether hitting `max_lines` with input left over is an error
+    pub(crate) report_truncation: bool,
+    /// Line ending handling, or `None` for the default of the input kind
+    pub(crate) line_endings: Option<LineEndings>,
+    pub(crate) carriage_returns: CarriageReturns,
+    pub(crate) buffering: Buffering,
+    pub(crate) tab_stops: TabStops,
+    pub(crate) tabs: Tabs,
+    pub(crate) decorations: Decorations,
+    pub(crate) color_depth: ColorDepth,
+    pub(crate) layer: Layer,
+    pub(crate) direction: Direction,
+    pub(crate) block_lines: usize,
+    pub(crate) fit: Fit,
+    /// Rainbow start position, or `None` for a random one
+    pub(crate) offset: Option<f64>,
+    /// lolcat's `--animate`, redrawing each line before moving on
+    pub(crate) animate: bool,
+    pub(crate) animation: Animation,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self {
+            command: None,
+            compat: false,
+            cat: false,
+            profile: None,
+            no_config: false,
+            inputs: Vec::new(),
+            restart_per_file: false,
+            frequency: 0.04,
+            spread: 4.0,
+            force: false,
+            max_lines: None,
+            report_truncation: false,
+            line_endings: None,
+            carriage_returns: CarriageReturns::Character,
+            buffering: Buffering::Auto,
+            tab_stops: TabStops::default(),
+            tabs: Tabs::Expand,
+            decorations: Decorations::default(),
+            color_depth: ColorDepth::Auto,
+            layer: Layer::Foreground,
+            direction: Direction::Diagonal,
+            block_lines: 200,
+            fit: Fit::Off,
+            offset: None,
+            animate: false,
+            animation: Animation {
+                duration: 12,
+                speed: 20.0,
+            },
+        }
+    }
+}
+
+impl Args {
+    /// The library configuration for these options, validated like
+    /// [`Config::try_new`]. Line endings are left to the caller, as their
+    /// default depends on the inputs.
+    pub(crate) fn config(&self) -> Result<Config, ConfigError> {
+        let mut config = Config::try_new(self.frequency, self.spread, self.force)?;
+        config.max_lines = self.max_lines;
+        config.carriage_returns = self.carriage_returns;
+        config.buffering = self.buffering;
+        config.tab_stops = self.tab_stops.clone();
+        config.tabs = self.tabs;
+        config.decorations = self.decorations;
+        config.color_depth = self.color_depth;
+        config.layer = self.layer;
+        config.direction = self.direction;
+        config.block_lines = self.block_lines;
+        config.fit = self.fit;
+        if let Some(offset) = self.offset {
+            config.set_offset(offset);
+        }
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Animation {
+    /// Number of frames per line
+    pub(crate) duration: usize,
+    /// Frames per second
+    pub(crate) speed: f64,
+}
+
+/// How an option changes [`Args`]
+#[derive(Clone, Copy)]
+pub(crate) enum Action {
+    /// An option without a value
+    Flag(fn(&mut Args)),
+    /// An option taking a value. `set` returns `None` if the value is
+    /// invalid, which the error message explains with `expected`.
+    Value {
+        name: &'static str,
+        expected: &'static str,
+        /// The words the value can be, for completions, if there are some
+        choices: &'static [&'static str],
+        set: fn(&mut Args, &str) -> Option<()>,
+    },
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct OptionSpec {
+    pub(crate) short: Option<char>,
+    pub(crate) long: Option<&'static str>,
+    pub(crate) action: Action,
+    pub(crate) help: &'static str,
+    /// Whether the option is left out of the help text
+    pub(crate) hidden: bool,
+}
+
+impl OptionSpec {
+    /// The option as shown in the help text, e.g. `-f, --frequency <FREQUENCY>`
+    pub(crate) fn synopsis(&self) -> String {
+        let mut synopsis = match self.short {
+            Some(short) => format!("-{short}"),
+            None => "  ".to_string(),
+        };
+        if let Some(long) = self.long {
+            synopsis.push_str(if self.short.is_some() { ", --" } else { "  --" });
+            synopsis.push_str(long);
+        }
+        if let Action::Value { name, .. } = self.action {
+            let _ = write!(synopsis, " <{name}>");
+        }
+        synopsis
+    }
+
+    /// The words the option's value can be, if there are some
+    pub(crate) fn choices(&self) -> &'static [&'static str] {
+        match self.action {
+            Action::Value { choices, .. } => choices,
+            Action::Flag(_) => &[],
+        }
+    }
+}
+
+/// Parse an option value
+fn value<T: FromStr>(value: &str) -> Option<T> {
+    value.parse().ok()
+}
+
+pub(crate) const NATIVE_OPTIONS: &[OptionSpec] = &[
+    OptionSpec {
+        short: Some('f'),
+        long: Some("frequency"),
+        action: Action::Value {
+            name: "FREQUENCY",
+            expected: "a floating point number",
+            choices: &[],
+            set: |args, v| {
+                args.frequency = value(v)?;
+                Some(())
+            },
+        },
+        help: "Color change frequency [default: 0.04]",
+        hidden: false,
+    },
+    OptionSpec {
+        short: Some('s'),
+        long: Some("spread"),
+        action: Action::Value {
+            name: "SPREAD",
+            expected: "a floating point number",
+            choices: &[],
+            set: |args, v| {
+                args.spread = value(v)?;
+                Some(())
+            },
+        },
+        help: "Rainbow spread [default: 4.0]",
+        hidden: false,
+    },
+    OptionSpec {
+        short: Some('F'),
+        long: Some("force"),
+        action: Action::Flag(|args| args.force = true),
+        help: "Force color even when stdout is not a tty",
+        hidden: false,
+    },
+    OptionSpec {
+        short: None,
+        long: Some("color-depth"),
+        action: Action::Value {
+            name: "DEPTH",
+            expected: "'auto', 'truecolor' or '256'",
+            choices: &["auto", "truecolor", "256"],
+            set: |args, v| {
+                args.color_depth = match v {
+                    "auto" => ColorDepth::Auto,
+                    "truecolor" => ColorDepth::TrueColor,
+                    "256" => ColorDepth::Color256,
+                    _ => return None,
+                };
+                Some(())
+            },
+        },
+        help: "Use 24-bit (truecolor) or 256 colors instead of what the terminal \
+               supports [default: auto]",
+        hidden: false,
+    },
+    OptionSpec {
+        short: None,
+        long: Some("direction"),
+        action: Action::Value {
+            name: "DIRECTION",
+            expected: "'diagonal', 'horizontal', 'vertical', 'radial', 'conical' or an angle \
+                       in degrees",
+            choices: &["diagonal", "horizontal", "vertical", "radial", "conical"],
+            set: |args, v| {
+                args.direction = match v {
+                    "diagonal" => Direction::Diagonal,
+                    "horizontal" => Direction::Horizontal,
+                    "vertical" => Direction::Vertical,
+                    "radial" => Direction::Radial,
+                    "conical" => Direction::Conical,
+                    _ => Direction::Angle(value(v).filter(|angle: &f64| angle.is_finite())?),
+                };
+                Some(())
+            },
+        },
+        help: "Run the rainbow along the lines (horizontal), down them (vertical), both \
+               (diagonal), at an angle in degrees clockwise from horizontal, or out from \
+               (radial) or around (conical) the centre of each block of lines \
+               [default: diagonal]",
+        hidden: false,
+    },
+    OptionSpec {
+        short: None,
+        long: Some("block-lines"),
+        action: Action::Value {
+            name: "N",
+            expected: "a positive integer",
+            choices: &[],
+            set: |args, v| {
+                args.block_lines = value(v)?;
+                Some(())
+            },
+        },
+        help: "Read up to N lines at a time to find the centre for radial and conical \
+               directions [default: 200]",
+        hidden: false,
+    },
+    OptionSpec {
+        short: None,
+        long: Some("fit"),
+        action: Action::Flag(|args| args.fit = Fit::Document),
+        help: "Choose the frequency so that one full rainbow spans the input",
+        hidden: false,
+    },
+    OptionSpec {
+        short: None,
+        long: Some("fit-paragraphs"),
+        action: Action::Flag(|args| args.fit = Fit::Paragraphs),
+        help: "Choose the frequency so that one full rainbow spans each paragraph",
+        hidden: false,
+    },
+    OptionSpec {
+        short: None,
+        long: Some("max-lines"),
+        action: Action::Value {
+            name: "N",
+            expected: "a non-negative integer",
+            choices: &[],
+            set: |args, v| {
+                args.max_lines = Some(value(v)?);
+                args.report_truncation = true;
+                Some(())
+            },
+        },
+        help: "Stop after N lines, failing if input was left over",
+        hidden: false,
+    },
+    OptionSpec {
+        short: None,
+        long: Some("head"),
+        action: Action::Value {
+            name: "N",
+            expected: "a non-negative integer",
+            choices: &[],
+            set: |args, v| {
+                args.max_lines = Some(value(v)?);
+                args.report_truncation = false;
+                Some(())
+            },
+        },
+        help: "Print only the first N lines",
+        hidden: false,
+    },
+    OptionSpec {
+        short: None,
+        long: Some("line-endings"),
+        action: Action::Value {
+            name: "MODE",
+            expected: "'preserve' or 'normalize'",
+            choices: &["preserve", "normalize"],
+            set: |args, v| {
+                args.line_endings = Some(match v {
+                    "preserve" => LineEndings::Preserve,
+                    "normalize" => LineEndings::Normalize,
+                    _ => return None,
+                });
+                Some(())
+            },
+        },
+        help: "Keep line endings as they are (preserve) or write them all as \\n \
+               (normalize); tabs are kept only with --keep-tabs [default: preserve for \
+               files, normalize for stdin]",
+        hidden: false,
+    },
+    OptionSpec {
+        short: None,
+        long: Some("progress"),
+        action: Action::Flag(|args| args.carriage_returns = CarriageReturns::Rewind),
+        help: "Restart the line's colors and flush on \\r or a move to the first \
+               column, so progress bars animate",
+        hidden: false,
+    },
+    OptionSpec {
+        short: None,
+        long: Some("line-buffered"),
+        action: Action::Flag(|args| args.buffering = Buffering::Line),
+        help: "Flush output after every line [default when stdout is a tty]",
+        hidden: false,
+    },
+    OptionSpec {
+        short: Some('u'),
+        long: Some("unbuffered"),
+        action: Action::Flag(|args| args.buffering = Buffering::Unbuffered),
+        help: "Flush output as soon as it is written",
+        hidden: false,
+    },
+    OptionSpec {
+        short: None,
+        long: Some("tabsize"),
+        action: Action::Value {
+            name: "N",
+            expected: "a positive integer",
+            choices: &[],
+            set: |args, v| {
+                args.tab_stops = TabStops::Every(value(v)?);
+                Some(())
+            },
+        },
+        help: "Put a tab stop every N columns [default: 8]",
+        hidden: false,
+    },
+    OptionSpec {
+        short: None,
+        long: Some("tab-stops"),
+        action: Action::Value {
+            name: "COLS",
+            expected: "comma-separated column numbers",
+            choices: &[],
+            set: |args, v| {
+                args.tab_stops = TabStops::List(
+                    v.split(',')
+                        .map(|stop| value(stop.trim()))
+                        .collect::<Option<_>>()?,
+                );
+                Some(())
+            },
+        },
+        help: "Put tab stops at a comma-separated list of columns",
+        hidden: false,
+    },
+    OptionSpec {
+        short: None,
+        long: Some("keep-tabs"),
+        action: Action::Flag(|args| args.tabs = Tabs::Preserve),
+        help: "Write tabs as tabs instead of spaces",
+        hidden: false,
+    },
+    OptionSpec {
+        short: None,
+        long: Some("restart-per-file"),
+        action: Action::Flag(|args| args.restart_per_file = true),
+        help: "Start the rainbow and line numbers over for each file instead of \
+               continuing them",
+        hidden: false,
+    },
+    OptionSpec {
+        short: Some('n'),
+        long: Some("number"),
+        action: Action::Flag(|args| {
+            if args.decorations.line_numbers == LineNumbers::Off {
+                args.decorations.line_numbers = LineNumbers::All;
+            }
+        }),
+        help: "Number all output lines",
+        hidden: false,
+    },
+    OptionSpec {
+        short: Some('b'),
+        long: Some("number-nonblank"),
+        action: Action::Flag(|args| args.decorations.line_numbers = LineNumbers::NonBlank),
+        help: "Number nonempty output lines, overriding -n",
+        hidden: false,
+    },
+    OptionSpec {
+        short: None,
+        long: Some("squeeze-blank"),
+        action: Action::Flag(|args| args.decorations.squeeze_blank = true),
+        help: "Suppress repeated empty output lines (-s with --cat)",
+        hidden: false,
+    },
+    OptionSpec {
+        short: Some('E'),
+        long: Some("show-ends"),
+        action: Action::Flag(|args| args.decorations.show_ends = true),
+        help: "Display $ at the end of each line",
+        hidden: false,
+    },
+    OptionSpec {
+        short: Some('T'),
+        long: Some("show-tabs"),
+        action: Action::Flag(|args| args.decorations.show_tabs = true),
+        help: "Display tabs as ^I",
+        hidden: false,
+    },
+    OptionSpec {
+        short: None,
+        long: Some("show-nonprinting"),
+        action: Action::Flag(|args| args.decorations.show_nonprinting = true),
+        help: "Use ^ and M- notation, except for tabs and newlines (-v with --cat)",
+        hidden: false,
+    },
+    OptionSpec {
+        short: Some('A'),
+        long: Some("show-all"),
+        action: Action::Flag(|args| {
+            args.decorations.show_nonprinting = true;
+            args.decorations.show_ends = true;
+            args.decorations.show_tabs = true;
+        }),
+        help: "Equivalent to --show-nonprinting --show-ends --show-tabs",
+        hidden: false,
+    },
+    OptionSpec {
+        short: None,
+        long: Some("cat"),
+        action: Action::Flag(|_| {}),
+        help: "Take -s and -v as cat does, as when run as cat; otherwise they are --spread and --version",
+        hidden: false,
+    },
+    OptionSpec {
+        short: None,
+        long: Some("compat"),
+        action: Action::Flag(|_| {}),
+        help: "Take the options of the original lolcat instead, as when run as lolcat",
+        hidden: false,
+    },
+    OptionSpec {
+        short: None,
+        long: Some("profile"),
+        action: Action::Value {
+            name: "NAME",
+            expected: "a profile name",
+            choices: &[],
+            set: |args, v| {
+                args.profile = Some(v.to_string());
+                Some(())
+            },
+        },
+        help: "Apply the settings of [profile.NAME] in the config file",
+        hidden: false,
+    },
+    OptionSpec {
+        short: None,
+        long: Some("no-config"),
+        action: Action::Flag(|args| args.no_config = true),
+        help: "Ignore the config file",
+        hidden: false,
+    },
+    OptionSpec {
+        short: Some('h'),
+        long: Some("help"),
+        action: Action::Flag(|args| args.command = Some(Command::Help)),
+        help: "Print help",
+        hidden: false,
+    },
+    OptionSpec {
+        short: Some('v'),
+        long: Some("version"),
+        action: Action::Flag(|args| args.command = Some(Command::Version)),
+        help: "Print version",
+        hidden: false,
+    },
+    OptionSpec {
+        short: None,
+        long: Some("generate"),
+        action: Action::Value {
+            name: "WHAT",
+            expected: "'completions' or 'man'",
+            choices: &["completions", "man"],
+            set: |args, v| {
+                args.command = Some(match v {
+                    "completions" => Command::Completions(None),
+                    "man" => Command::Man,
+                    _ => return None,
+                });
+                Some(())
+            },
+        },
+        help: "Print a completion script for the shell that follows, or a man page",
+        hidden: true,
+    },
+];
+
+/// The options of the original Ruby lolcat, for scripts written for it
+pub(crate) const COMPAT_OPTIONS: &[OptionSpec] = &[
+    OptionSpec {
+        short: Some('p'),
+        long: Some("spread"),
+        action: Action::Value {
+            name: "SPREAD",
+            expected: "a floating point number",
+            choices: &[],
+            set: |args, v| {
+                args.spread = value(v)?;
+                Some(())
+            },
+        },
+        help: "Rainbow spread [default: 3.0]",
+        hidden: false,
+    },
+    OptionSpec {
+        short: Some('F'),
+        long: Some("freq"),
+        action: Action::Value {
+            name: "FREQUENCY",
+            expected: "a floating point number",
+            choices: &[],
+            set: |args, v| {
+                args.frequency = value(v)?;
+                Some(())
+            },
+        },
+        help: "Rainbow frequency [default: 0.1]",
+        hidden: false,
+    },
+    OptionSpec {
+        short: Some('S'),
+        long: Some("seed"),
+        action: Action::Value {
+            name: "SEED",
+            expected: "an integer",
+            choices: &[],
+            set: |args, v| {
+                let seed: i64 = value(v)?;
+                // Seed 0 keeps the random start. lolcat moves a line along
+                // before coloring each line, the first included.
+                args.offset = (seed != 0).then_some(seed as f64 + 1.0);
+                Some(())
+            },
+        },
+        help: "Rainbow seed, 0 = random [default: 0]",
+        hidden: false,
+    },
+    OptionSpec {
+        short: Some('a'),
+        long: Some("animate"),
+        action: Action::Flag(|args| args.animate = true),
+        help: "Enable psychedelics",
+        hidden: false,
+    },
+    OptionSpec {
+        short: Some('d'),
+        long: Some("duration"),
+        action: Action::Value {
+            name: "DURATION",
+            expected: "a positive integer",
+            choices: &[],
+            set: |args, v| {
+                args.animation.duration = value(v).filter(|&duration| duration > 0)?;
+                Some(())
+            },
+        },
+        help: "Animation duration [default: 12]",
+        hidden: false,
+    },
+    OptionSpec {
+        short: Some('s'),
+        long: Some("speed"),
+        action: Action::Value {
+            name: "SPEED",
+            expected: "a positive number",
+            choices: &[],
+            set: |args, v| {
+                args.animation.speed =
+                    value(v).filter(|speed: &f64| speed.is_finite() && *speed > 0.0)?;
+                Some(())
+            },
+        },
+        help: "Animation speed [default: 20.0]",
+        hidden: false,
+    },
+    OptionSpec {
+        short: Some('i'),
+        long: Some("invert"),
+        action: Action::Flag(|args| args.layer = Layer::Background),
+        help: "Invert fg and bg",
+        hidden: false,
+    },
+    OptionSpec {
+        short: Some('t'),
+        long: Some("truecolor"),
+        action: Action::Flag(|args| args.color_depth = ColorDepth::TrueColor),
+        help: "24-bit (truecolor)",
+        hidden: false,
+    },
+    OptionSpec {
+        short: Some('f'),
+        long: Some("force"),
+        action: Action::Flag(|args| args.force = true),
+        help: "Force color even when stdout is not a tty",
+        hidden: false,
+    },
+    OptionSpec {
+        short: None,
+        long: Some("compat"),
+        action: Action::Flag(|_| {}),
+        help: "Take these options, the default when run as lolcat",
+        hidden: false,
+    },
+    OptionSpec {
+        short: Some('v'),
+        long: Some("version"),
+        action: Action::Flag(|args| args.command = Some(Command::Version)),
+        help: "Print version",
+        hidden: false,
+    },
+    OptionSpec {
+        short: Some('h'),
+        long: Some("help"),
+        action: Action::Flag(|args| args.command = Some(Command::Help)),
+        help: "Print help",
+        hidden: false,
+    },
+];
+
+/// Short options that `--cat` takes as `cat` does, with the long options
+/// they stand for then instead of those given in [`NATIVE_OPTIONS`]
+pub(crate) const CAT_SHORTS: &[(char, &str)] = &[('s', "squeeze-blank"), ('v', "show-nonprinting")];
+
+/// Options that only make sense on the command line
+const COMMAND_LINE_ONLY: &[&str] = &[
+    "cat",
+    "compat",
+    "profile",
+    "no-config",
+    "help",
+    "version",
+    "generate",
+];
+
+/// Parse the command line `args` following `program_name`, on top of
+/// `env_options`, the value of `LOLCAT_OPTS`, on top of the defaults from the
+/// config file
+pub(crate) fn parse_args(
+    program_name: &str,
+    env_options: Option<&str>,
+    args: Vec<String>,
+) -> Result<Args, String> {
+    // The command line names the profile, so it is parsed twice
+    let parsed = parse(program_name, args.clone())?;
+    // Scripts written for lolcat should behave as they would with it
+    if parsed.compat || parsed.command.is_some() {
+        return Ok(parsed);
+    }
+
+    // Its own pass, so a value option at the end takes no argument given on
+    // the command line as its value. Like the config file, it only sets
+    // defaults.
+    let env_error = |e| format!("LOLCAT_OPTS: {e}");
+    let env_args = split_options(env_options.unwrap_or_default()).map_err(env_error)?;
+    if let Some(name) = env_args.iter().find_map(|arg| {
+        let name = arg.strip_prefix("--")?;
+        let name = name.split_once('=').map_or(name, |(name, _)| name);
+        COMMAND_LINE_ONLY.contains(&name).then_some(name)
+    }) {
+        return Err(env_error(format!(
+            "'--{name}' is only taken on the command line"
+        )));
+    }
+    let options: Vec<OptionSpec> = NATIVE_OPTIONS
+        .iter()
+        .filter(|option| {
+            option
+                .long
+                .is_none_or(|long| !COMMAND_LINE_ONLY.contains(&long))
+        })
+        .copied()
+        .collect();
+    let mut defaults = if parsed.no_config {
+        Args::default()
+    } else {
+        config_file_defaults(parsed.profile.as_deref())?
+    };
+    defaults.cat = parsed.cat;
+    let defaults = parse_onto(&options, defaults, env_args).map_err(env_error)?;
+    if let Some(input) = defaults.inputs.first() {
+        return Err(env_error(format!(
+            "unexpected file name '{}'",
+            input.display()
+        )));
+    }
+    parse_onto(NATIVE_OPTIONS, defaults, args)
+}
+
+/// Split the value of `LOLCAT_OPTS` into arguments like a shell would:
+/// at unquoted whitespace, with `'...'` and `"..."` quoting and `\` escapes
+pub(crate) fn split_options(options: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    // The argument being read, if any
+    let mut arg: Option<String> = None;
+    let mut chars = options.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => args.extend(arg.take()),
+            '\'' => {
+                let arg = arg.get_or_insert_default();
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => return Err("unterminated single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let arg = arg.get_or_insert_default();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        // Only these are escaped within double quotes
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => arg.push(c),
+                            Some(c) => {
+                                arg.push('\\');
+                                arg.push(c);
+                            }
+                            None => return Err("unterminated double quote".to_string()),
+                        },
+                        Some(c) => arg.push(c),
+                        None => return Err("unterminated double quote".to_string()),
+                    }
+                }
+            }
+            '\\' => {
+                if let Some(c) = chars.next() {
+                    arg.get_or_insert_default().push(c);
+                }
+            }
+            c => arg.get_or_insert_default().push(c),
+        }
+    }
+    args.extend(arg);
+    Ok(args)
+}
+
+/// Option defaults from the config file, with the settings of `profile` on
+/// top of the top-level ones
+fn config_file_defaults(profile: Option<&str>) -> Result<Args, String> {
+    let mut defaults = Args::default();
+    let Some(path) = config_file::path() else {
+        return match profile {
+            Some(profile) => Err(format!("unknown profile '{profile}': no config file")),
+            None => Ok(defaults),
+        };
+    };
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound && profile.is_none() => {
+            return Ok(defaults);
+        }
+        Err(err) => return Err(format!("{}: {err}", path.display())),
+    };
+
+    let at_line =
+        |err: config_file::Error| format!("{}:{}: {}", path.display(), err.line, err.message);
+    let file = config_file::parse(&text).map_err(at_line)?;
+    apply_settings(&file.settings, &mut defaults).map_err(at_line)?;
+    if let Some(profile) = profile {
+        let settings = file
+            .profile(profile)
+            .ok_or_else(|| format!("{}: unknown profile '{profile}'", path.display()))?;
+        apply_settings(settings, &mut defaults).map_err(at_line)?;
+    }
+    Ok(defaults)
+}
+
+/// Apply config file settings, each named like the long option it sets.
+/// Flags are set with `true`. As there is no option to unset them, `false`
+/// is rejected rather than leaving them as they are.
+fn apply_settings(settings: &[Setting], args: &mut Args) -> Result<(), config_file::Error> {
+    for setting in settings {
+        let error = |message| config_file::Error {
+            line: setting.line,
+            message,
+        };
+        let key = setting.key.as_str();
+        let option = NATIVE_OPTIONS
+            .iter()
+            .find(|option| option.long == Some(key) && !COMMAND_LINE_ONLY.contains(&key))
+            .ok_or_else(|| {
+                let settings = NATIVE_OPTIONS
+                    .iter()
+                    .filter_map(|option| option.long)
+                    .filter(|long| !COMMAND_LINE_ONLY.contains(long));
+                error(match closest(key, settings) {
+                    Some(closest) => format!("unknown setting '{key}' (did you mean '{closest}'?)"),
+                    None => format!("unknown setting '{key}'"),
+                })
+            })?;
+
+        match (&option.action, &setting.value) {
+            (Action::Flag(set), Value::Boolean(true)) => set(args),
+            (Action::Flag(_), Value::Boolean(false)) => {
+                return Err(error(format!(
+                    "invalid value for '{key}': flags can only be set to true"
+                )));
+            }
+            (Action::Flag(_), _) => {
+                return Err(error(format!("invalid value for '{key}': expected true")));
+            }
+            (Action::Value { expected, set, .. }, value) => {
+                let text = setting_text(value).ok_or_else(|| {
+                    error(format!("invalid value for '{key}': expected {expected}"))
+                })?;
+                set(args, &text).ok_or_else(|| {
+                    error(format!(
+                        "invalid value '{text}' for '{key}': expected {expected}"
+                    ))
+                })?;
+            }
+        }
+        // Check each setting as it is made, to report the line at fault
+        args.config().map_err(|err| error(err.to_string()))?;
+    }
+    Ok(())
+}
+
+/// A setting's value as it would be written on the command line, with
+/// arrays comma-separated
+fn setting_text(value: &Value) -> Option<String> {
+    match value {
+        Value::Number(text) | Value::String(text) => Some(text.clone()),
+        Value::Array(values) => values
+            .iter()
+            .map(|value| match value {
+                Value::Number(text) | Value::String(text) => Some(text.as_str()),
+                Value::Boolean(_) | Value::Array(_) => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(|values| values.join(",")),
+        Value::Boolean(_) => None,
+    }
+}
+
+/// Parse `args`, which follow `program_name`. Stops early, with
+/// [`Args::command`] set, at `--help` or `--version`.
+pub(crate) fn parse(
+    program_name: &str,
+    args: impl IntoIterator<Item = String>,
+) -> Result<Args, String> {
+    let args: Vec<String> = args.into_iter().collect();
+
+    // Run as `lolcat` or `cat`, or asked to behave like it
+    let run_as = |name: &str, option: &str| {
+        Path::new(program_name)
+            .file_stem()
+            .is_some_and(|stem| stem == name)
+            || args
+                .iter()
+                .take_while(|arg| *arg != "--")
+                .any(|arg| arg == option)
+    };
+    let compat = run_as("lolcat", "--compat");
+    let (options, parsed) = if compat {
+        let args = Args {
+            compat: true,
+            frequency: 0.1,
+            spread: 3.0,
+            // lolcat only writes truecolor with `-t`
+            color_depth: ColorDepth::Color256,
+            direction: Direction::Lolcat,
+            line_endings: Some(LineEndings::Normalize),
+            ..Args::default()
+        };
+        (COMPAT_OPTIONS, args)
+    } else {
+        let args = Args {
+            cat: run_as("cat", "--cat"),
+            ..Args::default()
+        };
+        (NATIVE_OPTIONS, args)
+    };
+    parse_onto(options, parsed, args)
+}
+
+/// Parse `args` as `options`, on top of the settings in `parsed`
+fn parse_onto(options: &[OptionSpec], mut parsed: Args, args: Vec<String>) -> Result<Args, String> {
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            parsed.inputs.extend(args.by_ref().map(PathBuf::from));
+        } else if let Some(long) = arg.strip_prefix("--") {
+            let (name, inline_value) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (long, None),
+            };
+            let option = options
+                .iter()
+                .find(|option| option.long == Some(name))
+                .ok_or_else(|| unknown_long_option(name, options))?;
+            apply(
+                option,
+                &format!("--{name}"),
+                inline_value,
+                &mut args,
+                &mut parsed,
+            )?;
+        } else if let Some(shorts) = arg.strip_prefix('-')
+            && !shorts.is_empty()
+        {
+            for (i, short) in shorts.char_indices() {
+                let long = CAT_SHORTS
+                    .iter()
+                    .find(|&&(cat_short, _)| parsed.cat && cat_short == short)
+                    .map(|&(_, long)| long);
+                let option = options
+                    .iter()
+                    .find(|option| match long {
+                        Some(long) => option.long == Some(long),
+                        None => option.short == Some(short),
+                    })
+                    .ok_or_else(|| format!("unknown option: -{short}"))?;
+                // A value option takes the rest of the bundle as its value
+                let rest = &shorts[i + short.len_utf8()..];
+                let takes_value = matches!(option.action, Action::Value { .. });
+                let inline_value = (takes_value && !rest.is_empty()).then(|| rest.to_string());
+                apply(
+                    option,
+                    &format!("-{short}"),
+                    inline_value,
+                    &mut args,
+                    &mut parsed,
+                )?;
+                if takes_value || parsed.command.is_some() {
+                    break;
+                }
+            }
+        } else {
+            parsed.inputs.push(PathBuf::from(arg));
+        }
+        if let Some(command) = parsed.command {
+            if command == Command::Completions(None) {
+                let shell = args
+                    .next()
+                    .ok_or("missing shell for '--generate completions'")?;
+                parsed.command = Some(Command::Completions(Some(shell.parse()?)));
+            }
+            break;
+        }
+    }
+
+    Ok(parsed)
+}
+
+/// Apply `option`, given on the command line as `given`, taking its value
+/// from `inline_value` or else the next argument
+fn apply(
+    option: &OptionSpec,
+    given: &str,
+    inline_value: Option<String>,
+    args: &mut impl Iterator<Item = String>,
+    parsed: &mut Args,
+) -> Result<(), String> {
+    match option.action {
+        Action::Flag(set) => {
+            if inline_value.is_some() {
+                return Err(format!("option '{given}' does not take a value"));
+            }
+            set(parsed);
+        }
+        Action::Value { expected, set, .. } => {
+            let value = inline_value
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("missing value for '{given}'"))?;
+            set(parsed, &value).ok_or_else(|| {
+                format!("invalid value '{value}' for '{given}': expected {expected}")
+            })?;
+        }
+    }
+    Ok(())
+}
+
+fn unknown_long_option(name: &str, options: &[OptionSpec]) -> String {
+    match closest(name, options.iter().filter_map(|option| option.long)) {
+        Some(long) => format!("unknown option: --{name} (did you mean --{long}?)"),
+        None => format!("unknown option: --{name}"),
+    }
+}
+
+/// The candidate `name` is most likely a typo of, if any
+fn closest<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    candidates
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .min()
+        // Close enough to be a typo rather than a different name
+        .filter(|&(distance, _)| distance <= 2.max(name.len() / 3))
+        .map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance between `a` and `b`, in characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, a) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, &b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != b);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// Append `text` to `help`, word-wrapped to [`HELP_WIDTH`] with continuation
+/// lines indented to [`HELP_INDENT`]
+fn push_wrapped(help: &mut String, text: &str) {
+    let mut column = HELP_INDENT;
+    for (i, word) in text.split(' ').enumerate() {
+        if i > 0 {
+            if column + 1 + word.len() > HELP_WIDTH {
+                help.push('\n');
+                help.push_str(&" ".repeat(HELP_INDENT));
+                column = HELP_INDENT;
+            } else {
+                help.push(' ');
+                column += 1;
+            }
+        }
+        help.push_str(word);
+        column += word.len();
+    }
+    help.push('\n');
+}
+
+/// The `--help` text, for the original lolcat's options if `compat`
+pub(crate) fn help(program_name: &str, compat: bool) -> String {
+    let (mut help, options) = if compat {
+        let intro = format!(
+            "Usage: {program_name} [OPTION]... [FILE]...\n\
+            \n\
+            Concatenate FILE(s), or standard input, to standard output.\n\
+            With no FILE, or when FILE is -, read standard input.\n\
+            \n\
+            Options (compatible with the original lolcat):\n"
+        );
+        (intro, COMPAT_OPTIONS)
+    } else {
+        let intro = format!(
+            "{ABOUT}\n\
+            \n\
+            Usage: {program_name} [OPTIONS] [--] [FILE]...\n\
+            \n\
+            Arguments:\n\
+            \x20 [FILE]...  input files, read in order; - or none for stdin\n\
+            \n\
+            Options:\n"
+        );
+        (intro, NATIVE_OPTIONS)
+    };
+    for option in options.iter().filter(|option| !option.hidden) {
+        let synopsis = option.synopsis();
+        let padding = HELP_INDENT - 2;
+        if synopsis.len() + 2 > padding {
+            let _ = write!(help, "  {synopsis}\n{:HELP_INDENT$}", "");
+        } else {
+            let _ = write!(help, "  {synopsis:<padding$}");
+        }
+        push_wrapped(&mut help, option.help);
+    }
+    help
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_native(args: &[&str]) -> Result<Args, String> {
+        parse("lolcat-ultra", args.iter().map(ToString::to_string))
+    }
+
+    #[test]
+    fn values_can_be_attached_or_separate() {
+        for args in [
+            &["--frequency=0.5"][..],
+            &["--frequency", "0.5"],
+            &["-f0.5"],
+            &["-f", "0.5"],
+        ] {
+            let parsed = parse_native(args).unwrap();
+            assert!((parsed.frequency - 0.5).abs() < f64::EPSILON, "{args:?}");
+        }
+    }
+
+    #[test]
+    fn short_flags_can_be_bundled() {
+        let parsed = parse_native(&["-nEFf", "0.5", "file"]).unwrap();
+        assert_eq!(parsed.decorations.line_numbers, LineNumbers::All);
+        assert!(parsed.decorations.show_ends);
+        assert!(parsed.force);
+        assert!((parsed.frequency - 0.5).abs() < f64::EPSILON);
+        assert_eq!(parsed.inputs, [PathBuf::from("file")]);
+    }
+
+    #[test]
+    fn double_dash_ends_options() {
+        let parsed = parse_native(&["-n", "--", "-F", "--help", "-"]).unwrap();
+        assert!(!parsed.force);
+        assert_eq!(parsed.command, None);
+        assert_eq!(parsed.inputs, ["-F", "--help", "-"].map(PathBuf::from));
+    }
+
+    #[test]
+    fn errors_name_the_option() {
+        assert_eq!(
+            parse_native(&["--frequncy", "0.5"]).unwrap_err(),
+            "unknown option: --frequncy (did you mean --frequency?)"
+        );
+        assert_eq!(
+            parse_native(&["--colour"]).unwrap_err(),
+            "unknown option: --colour"
+        );
+        assert_eq!(parse_native(&["-nX"]).unwrap_err(), "unknown option: -X");
+        assert_eq!(
+            parse_native(&["--force=yes"]).unwrap_err(),
+            "option '--force' does not take a value"
+        );
+        assert_eq!(
+            parse_native(&["-nf"]).unwrap_err(),
+            "missing value for '-f'"
+        );
+        assert_eq!(
+            parse_native(&["--head=-1"]).unwrap_err(),
+            "invalid value '-1' for '--head': expected a non-negative integer"
+        );
+    }
+
+    #[test]
+    fn cat_mode_takes_cat_short_options() {
+        let parsed = parse_native(&["-s", "3", "-v"]).unwrap();
+        assert!((parsed.spread - 3.0).abs() < f64::EPSILON);
+        assert_eq!(parsed.command, Some(Command::Version));
+
+        for (program_name, args) in [("lolcat-ultra", &["--cat", "-nsv"][..]), ("cat", &["-sv"])] {
+            let parsed = parse(program_name, args.iter().map(ToString::to_string)).unwrap();
+            assert!(parsed.decorations.squeeze_blank);
+            assert!(parsed.decorations.show_nonprinting);
+            assert_eq!(parsed.command, None);
+        }
+        let parsed = parse_native(&["--cat", "--spread", "3"]).unwrap();
+        assert!((parsed.spread - 3.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn choices_are_taken() {
+        for option in NATIVE_OPTIONS.iter().chain(COMPAT_OPTIONS) {
+            if let Action::Value { set, choices, .. } = option.action {
+                for choice in choices {
+                    assert!(set(&mut Args::default(), choice).is_some(), "{choice}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn compat_mode_takes_lolcat_options() {
+        let parsed = parse(
+            "/usr/bin/lolcat",
+            ["-p", "2", "-F0.3", "-S", "7", "-ait"].map(String::from),
+        )
+        .unwrap();
+        assert!(parsed.compat);
+        assert!((parsed.spread - 2.0).abs() < f64::EPSILON);
+        assert!((parsed.frequency - 0.3).abs() < f64::EPSILON);
+        // The first line is colored a line along from the seed
+        assert_eq!(parsed.offset, Some(8.0));
+        assert_eq!(parsed.direction, Direction::Lolcat);
+        assert!(parsed.animate);
+        assert_eq!(parsed.layer, Layer::Background);
+        assert_eq!(parsed.color_depth, ColorDepth::TrueColor);
+
+        let parsed = parse("lolcat-ultra", ["-s", "5", "--compat"].map(String::from)).unwrap();
+        assert!(parsed.compat);
+        assert!((parsed.animation.speed - 5.0).abs() < f64::EPSILON);
+        assert_eq!(parsed.color_depth, ColorDepth::Color256);
+    }
+
+    fn apply_file(text: &str, profile: Option<&str>) -> Result<Args, config_file::Error> {
+        let file = config_file::parse(text)?;
+        let mut args = Args::default();
+        apply_settings(&file.settings, &mut args)?;
+        if let Some(profile) = profile {
+            apply_settings(file.profile(profile).unwrap(), &mut args)?;
+        }
+        Ok(args)
+    }
+
+    #[test]
+    fn config_file_sets_defaults() {
+        let text = "frequency = 0.5\n\
+                    color-depth = \"256\"\n\
+                    number = true\n\
+                    \n\
+                    [profile.wide]\n\
+                    spread = 10\n\
+                    tab-stops = [4, 12]\n";
+        let args = apply_file(text, None).unwrap();
+        assert!((args.frequency - 0.5).abs() < f64::EPSILON);
+        assert_eq!(args.color_depth, ColorDepth::Color256);
+        assert_eq!(args.decorations.line_numbers, LineNumbers::All);
+        assert!((args.spread - 4.0).abs() < f64::EPSILON);
+
+        let args = apply_file(text, Some("wide")).unwrap();
+        assert!((args.spread - 10.0).abs() < f64::EPSILON);
+        assert_eq!(args.tab_stops, TabStops::List(vec![4, 12]));
+
+        // The command line overrides the file
+        let args = parse_onto(NATIVE_OPTIONS, args, vec!["-s3".to_string()]).unwrap();
+        assert!((args.spread - 3.0).abs() < f64::EPSILON);
+        assert!((args.frequency - 0.5).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn config_file_errors_point_to_the_line() {
+        for (text, line, message) in [
+            ("\nspread = -1", 2, "invalid spread: -1"),
+            ("tabsize = 0", 1, "invalid tab size: 0"),
+            (
+                "freqency = 1",
+                1,
+                "unknown setting 'freqency' (did you mean 'frequency'?)",
+            ),
+            ("profile = \"x\"", 1, "unknown setting 'profile'"),
+            (
+                "force = \"yes\"",
+                1,
+                "invalid value for 'force': expected true",
+            ),
+            (
+                "\n\nforce = false",
+                3,
+                "invalid value for 'force': flags can only be set to true",
+            ),
+            (
+                "line-endings = \"crlf\"",
+                1,
+                "invalid value 'crlf' for 'line-endings': expected 'preserve' or 'normalize'",
+            ),
+        ] {
+            let err = apply_file(text, None).unwrap_err();
+            assert_eq!(
+                (err.line, err.message.as_str()),
+                (line, message),
+                "{text:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn command_line_overrides_lolcat_opts() {
+        let parse = |options, args: &[&str]| {
+            let args = ["--no-config"].iter().chain(args).map(ToString::to_string);
+            parse_args("lolcat-ultra", Some(options), args.collect())
+        };
+        let parsed = parse("-f 0.5 --spread=2 -n", &["-s", "3", "file"]).unwrap();
+        assert!((parsed.spread - 3.0).abs() < f64::EPSILON);
+        assert!((parsed.frequency - 0.5).abs() < f64::EPSILON);
+        assert_eq!(parsed.decorations.line_numbers, LineNumbers::All);
+        assert_eq!(parsed.inputs, [PathBuf::from("file")]);
+
+        // A value option at the end takes nothing from the command line
+        assert_eq!(
+            parse("--spread", &["file"]).unwrap_err(),
+            "LOLCAT_OPTS: missing value for '--spread'"
+        );
+        assert_eq!(
+            parse("-f 'open", &[]).unwrap_err(),
+            "LOLCAT_OPTS: unterminated single quote"
+        );
+        assert_eq!(
+            parse("--profile=dark", &[]).unwrap_err(),
+            "LOLCAT_OPTS: '--profile' is only taken on the command line"
+        );
+        assert_eq!(
+            parse("-h", &[]).unwrap_err(),
+            "LOLCAT_OPTS: unknown option: -h"
+        );
+        assert_eq!(
+            parse("-- file", &[]).unwrap_err(),
+            "LOLCAT_OPTS: unexpected file name 'file'"
+        );
+    }
+
+    #[test]
+    fn compat_mode_ignores_lolcat_opts() {
+        let args = ["-p", "2"].map(String::from).to_vec();
+        let parsed = parse_args("lolcat", Some("-f 'open"), args).unwrap();
+        assert!(parsed.compat);
+        assert!((parsed.spread - 2.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn options_are_split_like_a_shell_would() {
+        assert_eq!(
+            split_options(r#"  -n --tab-stops "4, 8" -f'0.'5 a\ b "\"\x" "" "#).unwrap(),
+            ["-n", "--tab-stops", "4, 8", "-f0.5", "a b", "\"\\x", ""]
+        );
+        assert_eq!(split_options("").unwrap(), Vec::<String>::new());
+        assert_eq!(
+            split_options("-f 'open").unwrap_err(),
+            "unterminated single quote"
+        );
+    }
+
+    #[test]
+    fn help_lists_every_option() {
+        for (options, compat) in [(NATIVE_OPTIONS, false), (COMPAT_OPTIONS, true)] {
+            let help = help("lolcat-ultra", compat);
+            for option in options {
+                assert_eq!(
+                    help.contains(&option.synopsis()),
+                    !option.hidden,
+                    "{}",
+                    option.synopsis()
+                );
+            }
+            assert!(help.lines().all(|line| line.len() <= HELP_WIDTH), "{help}");
+        }
+    }
+}
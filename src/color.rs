@@ -1,11 +1,26 @@
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
 pub enum ColorMode {
+    #[cfg_attr(feature = "serde", serde(rename = "truecolor"))]
     TrueColor,
     Color256,
     NoColor,
 }
 
+#[must_use]
 pub fn detect_color_support(force_color: bool) -> ColorMode {
+    detect_color_support_for(&std::io::stdout(), force_color)
+}
+
+/// Detect color support for output written to `stream`
+pub(crate) fn detect_color_support_for(
+    stream: &impl std::io::IsTerminal,
+    force_color: bool,
+) -> ColorMode {
     use std::env;
 
     // Normalize once
@@ -46,16 +61,16 @@ pub fn detect_color_support(force_color: bool) -> ColorMode {
         };
     }
 
-    // If stdout is not a tty and we haven't been forced, disable color
-    if !std::io::IsTerminal::is_terminal(&std::io::stdout()) {
+    // If the stream is not a tty and we haven't been forced, disable color
+    if !stream.is_terminal() {
         return ColorMode::NoColor;
     }
 
     // TERM=dumb/unknown disables unless forced
-    if let Some(ref t) = term_lower {
-        if t == "dumb" || t == "unknown" {
-            return ColorMode::NoColor;
-        }
+    if let Some(ref t) = term_lower
+        && (t == "dumb" || t == "unknown")
+    {
+        return ColorMode::NoColor;
     }
 
     // Strong truecolor signals
@@ -105,11 +120,11 @@ pub fn detect_color_support(force_color: bool) -> ColorMode {
     }
 
     // 8) Basic color signals from TERM
-    if let Some(ref t) = term_lower {
-        if t.contains("xterm") || t.contains("ansi") || t.contains("vt100") || t.contains("color") {
-            // If it wasn't explicitly 256color, use 256 color support.
-            return ColorMode::Color256;
-        }
+    if let Some(ref t) = term_lower
+        && (t.contains("xterm") || t.contains("ansi") || t.contains("vt100") || t.contains("color"))
+    {
+        // If it wasn't explicitly 256color, use 256 color support.
+        return ColorMode::Color256;
     }
 
     // 9) CI environments: enable at least 256 color
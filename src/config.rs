@@ -1,21 +1,383 @@
 use std::fmt;
+use std::io::IsTerminal;
+
+use crate::color::{ColorMode, detect_color_support_for};
 
 /// Configuration for the rainbow effect
+///
+/// With the `serde` feature, it is serialized with the offset and
+/// `force_color` as plain fields, and validated like [`Config::try_new`]
+/// when deserialized. Fields other than `frequency` and `spread` may be left
+/// out for their defaults, and a missing `offset` gives a random one. A
+/// random offset is left out when serializing, so every deserialized copy
+/// draws its own.
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "ConfigRepr", into = "ConfigRepr")
+)]
 pub struct Config {
     /// Frequency of color changes (higher values mean faster color transitions)
     pub frequency: f64,
     /// Number of characters per rainbow spread
     pub spread: f64,
-    /// Random offset for the starting color
+    /// Maximum number of lines to output, or `None` for no limit
+    pub max_lines: Option<usize>,
+    /// Number of lines before the input, so that coloring it continues the
+    /// rainbow of earlier output (e.g. the previous file)
+    pub first_line: usize,
+    /// How line endings in the input are written to the output
+    pub line_endings: LineEndings,
+    /// How a `\r` that does not end a line is colored
+    pub carriage_returns: CarriageReturns,
+    /// When colored output is flushed
+    pub buffering: Buffering,
+    /// Where tabs advance to
+    pub tab_stops: TabStops,
+    /// Whether tabs are written as spaces or kept as tab bytes
+    pub tabs: Tabs,
+    /// `cat`-style line numbers and markers added to the text
+    pub decorations: Decorations,
+    /// Which color sequences are written when the output is colored
+    pub color_depth: ColorDepth,
+    /// Whether the rainbow colors the text or the background behind it
+    pub layer: Layer,
+    /// Which way the rainbow runs across the text
+    pub direction: Direction,
+    /// Most lines buffered at once to measure a block of text for
+    /// [`Direction::Radial`] and [`Direction::Conical`]. Longer input is
+    /// colored block by block, each around its own centre. Not used when
+    /// [`Config::fit`] decides what the text is instead.
+    pub block_lines: usize,
+    /// Whether the frequency is chosen so that one full rainbow spans the
+    /// text, instead of [`Config::frequency`]. Centred directions are then
+    /// placed around the centre of the fitted text.
+    pub fit: Fit,
+    /// Rainbow position of the first line, random unless set
     pub(crate) random_offset: f64,
+    /// Whether the offset was set with [`Config::set_offset`], so it is
+    /// serialized instead of drawn again
+    pub(crate) offset_set: bool,
     /// Force color output even when stdout is not a tty
     pub(crate) force_color: bool,
 }
 
+/// How line endings are written when coloring input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum LineEndings {
+    /// Write every line ending as `\n`, and end the last line with `\n` even
+    /// if the input does not
+    #[default]
+    Normalize,
+    /// Keep `\r\n` line endings and a missing final newline. Tabs are still
+    /// expanded unless [`Tabs::Preserve`] is set too; with both, the output
+    /// with escape sequences stripped is byte-identical to the input.
+    Preserve,
+}
+
+/// How a `\r` that is not part of a `\r\n` line ending is handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum CarriageReturns {
+    /// Color it like any other character
+    #[default]
+    Character,
+    /// Return to the start of the line: write it uncolored, restart the
+    /// rainbow at the line's first color and flush the output, so progress
+    /// bars redrawn with `\r` animate through the pipe. Escape sequences
+    /// moving the cursor to the first column (`ESC [ G`) are taken the same
+    /// way.
+    Rewind,
+}
+
+/// When buffered output is flushed to the underlying writer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Buffering {
+    /// Line-buffered when writing to a terminal through `process_input`,
+    /// otherwise block-buffered but flushed whenever all input read so far
+    /// has been written, so output never waits on an idle input. That is
+    /// every time the reader's buffer runs dry, so for input that never
+    /// keeps a read waiting, like a regular file, [`Buffering::Block`] saves
+    /// the flushes.
+    #[default]
+    Auto,
+    /// Flush only when the output buffer is full, for maximum throughput
+    Block,
+    /// Flush after every line
+    Line,
+    /// Flush as soon as anything has been written
+    Unbuffered,
+}
+
+/// Tab stop positions, in columns from the start of the line
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum TabStops {
+    /// A tab stop every `n` columns
+    Every(usize),
+    /// Tab stops at the listed columns, in increasing order. Past the last
+    /// stop a tab advances a single column, like `expand -t`.
+    List(Vec<usize>),
+}
+
+impl TabStops {
+    /// Number of columns a tab at `column` advances to reach the next stop
+    ///
+    /// Always at least one column. Stops that are not valid, which
+    /// processing rejects but [`RainbowWriter`](crate::RainbowWriter) and
+    /// [`Config::spans`] take as they are, advance a single column where
+    /// there is no stop to reach.
+    #[must_use]
+    #[inline]
+    pub fn width_at(&self, column: usize) -> usize {
+        match self {
+            Self::Every(0) => 1,
+            Self::Every(n) => n - column % n,
+            Self::List(stops) => {
+                let next = stops.partition_point(|&stop| stop <= column);
+                stops
+                    .get(next)
+                    .and_then(|stop| stop.checked_sub(column))
+                    .filter(|&width| width > 0)
+                    .unwrap_or(1)
+            }
+        }
+    }
+
+    fn is_valid(&self) -> bool {
+        match self {
+            Self::Every(n) => *n > 0,
+            Self::List(stops) => {
+                !stops.is_empty() && stops[0] > 0 && stops.windows(2).all(|w| w[0] < w[1])
+            }
+        }
+    }
+}
+
+impl Default for TabStops {
+    fn default() -> Self {
+        Self::Every(8)
+    }
+}
+
+/// How tabs are written when coloring input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Tabs {
+    /// Replace each tab with colored spaces up to the next tab stop
+    #[default]
+    Expand,
+    /// Keep the tab byte, advancing the rainbow by the columns it spans
+    Preserve,
+}
+
+/// Which color sequences are written when the output is colored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum ColorDepth {
+    /// Whatever the terminal is detected to support
+    #[default]
+    Auto,
+    /// 24-bit color sequences
+    #[cfg_attr(feature = "serde", serde(rename = "truecolor"))]
+    TrueColor,
+    /// 256-color palette sequences
+    #[cfg_attr(feature = "serde", serde(rename = "256", alias = "color256"))]
+    Color256,
+}
+
+/// What the rainbow colors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Layer {
+    /// The text itself
+    #[default]
+    Foreground,
+    /// The background behind the text, leaving the text in the terminal's
+    /// default color. The background is reset before each line ending, so
+    /// it does not bleed into the rest of the line.
+    Background,
+}
+
+/// Which way the rainbow runs across the text
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Direction {
+    /// Along the lines and down them at once: each column is `1 / spread`
+    /// further along the rainbow and each line starts `spread` further along
+    /// than the one above
+    #[default]
+    Diagonal,
+    /// Along the lines only, so every line starts with the same color
+    Horizontal,
+    /// Down the lines only, so every line is a single color
+    Vertical,
+    /// At an angle in degrees, clockwise from left to right: the column step
+    /// is weighted by its cosine and the line step by its sine, so 0 is
+    /// horizontal, 90 vertical, and 180 and 270 run the other way
+    Angle(f64),
+    /// Outwards from the centre of the text, one line or two columns further
+    /// out being `spread` further along the rainbow
+    Radial,
+    /// Around the centre of the text, clockwise from the right, with one
+    /// full rainbow per turn
+    Conical,
+    /// Like [`Direction::Diagonal`], but each line starts one further along
+    /// the rainbow than the one above whatever the spread, as in the
+    /// original lolcat
+    Lolcat,
+}
+
+impl Direction {
+    /// Whether the rainbow is placed around the centre of the text, which
+    /// takes buffering a block of it to measure.
+    ///
+    /// Only the `process_input` functions buffer; [`RainbowWriter`] and
+    /// [`Config::spans`] color text in these directions like
+    /// [`Direction::Diagonal`].
+    ///
+    /// [`RainbowWriter`]: crate::RainbowWriter
+    #[must_use]
+    pub fn is_centred(self) -> bool {
+        matches!(self, Self::Radial | Self::Conical)
+    }
+
+    /// Weights of the column and line steps
+    fn weights(self) -> (f64, f64) {
+        match self {
+            Self::Diagonal | Self::Radial | Self::Conical | Self::Lolcat => (1.0, 1.0),
+            Self::Horizontal => (1.0, 0.0),
+            Self::Vertical => (0.0, 1.0),
+            Self::Angle(degrees) => {
+                let (sin, cos) = degrees.to_radians().sin_cos();
+                // Keep right angles exact, so no stray drift is left over
+                let snap = |weight: f64| if weight.abs() < 1e-12 { 0.0 } else { weight };
+                (snap(cos), snap(sin))
+            }
+        }
+    }
+}
+
+/// How much text the rainbow is fitted to, so that it runs through all its
+/// colors exactly once across it.
+///
+/// The text is buffered to measure it, up to 4 MiB at a time; longer text
+/// is fitted a piece at a time. Only the `process_input` functions fit the
+/// rainbow; [`RainbowWriter`] and [`Config::spans`] keep the frequency.
+///
+/// [`RainbowWriter`]: crate::RainbowWriter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Fit {
+    /// Keep the frequency, so longer text cycles through more rainbows
+    #[default]
+    Off,
+    /// Fit the rainbow to the whole input
+    Document,
+    /// Fit the rainbow to each paragraph, a paragraph being the lines
+    /// between empty ones
+    Paragraphs,
+}
+
+/// Which lines get a line number, like `cat -n` and `cat -b`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum LineNumbers {
+    /// No line numbers
+    #[default]
+    Off,
+    /// Number every line
+    All,
+    /// Number only lines that are not empty
+    NonBlank,
+}
+
+/// `cat`-style decorations, added to the text before it is colored so they
+/// follow the rainbow like the rest of the line. Applied by the
+/// `process_input` functions, also without color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Decorations {
+    /// Prefix lines with their number, right-aligned in six columns and
+    /// followed by a tab (`cat -n`, `cat -b`)
+    pub line_numbers: LineNumbers,
+    /// Drop empty lines that follow another empty line (`cat -s`)
+    pub squeeze_blank: bool,
+    /// Write `$` at the end of each line (`cat -E`)
+    pub show_ends: bool,
+    /// Write tabs as `^I` (`cat -T`)
+    pub show_tabs: bool,
+    /// Write control characters as `^X` and bytes above 127 as `M-` followed
+    /// by the byte below 128, leaving tabs and newlines alone (`cat -v`).
+    /// Escape sequences and `\r` are shown rather than interpreted.
+    pub show_nonprinting: bool,
+}
+
+impl Decorations {
+    /// Whether any decoration is enabled
+    #[must_use]
+    pub fn any(&self) -> bool {
+        *self != Self::default()
+    }
+}
+
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum ConfigError {
     InvalidFrequency(f64),
     InvalidSpread(f64),
+    InvalidOffset(f64),
+    InvalidTabStops(TabStops),
+    InvalidAngle(f64),
+    InvalidBlockLines(usize),
 }
 
 impl fmt::Display for ConfigError {
@@ -39,12 +401,37 @@ impl fmt::Display for ConfigError {
                     write!(f, "invalid spread: {spread}")
                 }
             }
+            Self::InvalidOffset(offset) => {
+                if offset.is_infinite() {
+                    write!(f, "invalid offset: infinite")
+                } else {
+                    write!(f, "invalid offset: NaN")
+                }
+            }
+            Self::InvalidTabStops(TabStops::Every(n)) => {
+                write!(f, "invalid tab size: {n}")
+            }
+            Self::InvalidTabStops(TabStops::List(_)) => {
+                write!(f, "invalid tab stops: must be positive and increasing")
+            }
+            Self::InvalidAngle(angle) => {
+                if angle.is_infinite() {
+                    write!(f, "invalid angle: infinite")
+                } else {
+                    write!(f, "invalid angle: NaN")
+                }
+            }
+            Self::InvalidBlockLines(lines) => write!(f, "invalid block size: {lines} lines"),
         }
     }
 }
 
 impl std::error::Error for ConfigError {}
 
+/// Default for [`Config::block_lines`]: enough for banner art, while a
+/// block of long lines still fits in memory comfortably
+const DEFAULT_BLOCK_LINES: usize = 200;
+
 /// Generate a pseudo-random offset based on process ID
 ///
 /// Uses process ID with Knuth's multiplicative hash for fast, deterministic randomness
@@ -68,19 +455,113 @@ impl Config {
     ///
     /// Returns `ConfigError` if frequency or spread are not finite positive numbers
     pub fn try_new(frequency: f64, spread: f64, force_color: bool) -> Result<Self, ConfigError> {
-        if !frequency.is_finite() || frequency <= 0.0 {
-            return Err(ConfigError::InvalidFrequency(frequency));
-        }
-        if !spread.is_finite() || spread <= 0.0 {
-            return Err(ConfigError::InvalidSpread(spread));
-        }
-
-        Ok(Self {
+        let config = Self {
             frequency,
             spread,
+            max_lines: None,
+            first_line: 0,
+            line_endings: LineEndings::Normalize,
+            carriage_returns: CarriageReturns::Character,
+            buffering: Buffering::Auto,
+            tab_stops: TabStops::Every(8),
+            tabs: Tabs::Expand,
+            decorations: Decorations::default(),
+            color_depth: ColorDepth::Auto,
+            layer: Layer::Foreground,
+            direction: Direction::Diagonal,
+            block_lines: DEFAULT_BLOCK_LINES,
+            fit: Fit::Off,
             random_offset: generate_random_offset(),
+            offset_set: false,
             force_color,
-        })
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Rainbow position of the first line; random unless set with
+    /// [`Config::set_offset`]
+    #[must_use]
+    pub fn offset(&self) -> f64 {
+        self.random_offset
+    }
+
+    /// Start the rainbow at `offset` instead of a random position, so the
+    /// output is the same on every run
+    pub fn set_offset(&mut self, offset: f64) {
+        self.random_offset = offset;
+        self.offset_set = true;
+    }
+
+    /// Whether output is colored even when it is not a terminal
+    #[must_use]
+    pub fn force_color(&self) -> bool {
+        self.force_color
+    }
+
+    /// Color output even when it is not a terminal, or only when it is
+    pub fn set_force_color(&mut self, force_color: bool) {
+        self.force_color = force_color;
+    }
+
+    /// Color mode for output written to `stream`
+    pub(crate) fn color_mode_for(&self, stream: &impl IsTerminal) -> ColorMode {
+        let detected = detect_color_support_for(stream, self.force_color);
+        match self.color_depth {
+            ColorDepth::TrueColor if detected != ColorMode::NoColor => ColorMode::TrueColor,
+            ColorDepth::Color256 if detected != ColorMode::NoColor => ColorMode::Color256,
+            _ => detected,
+        }
+    }
+
+    /// Rainbow position at the start of input line `line`
+    #[inline]
+    pub(crate) fn line_start_pos(&self, line: usize) -> f64 {
+        ((self.first_line + line) as f64) * self.line_step() + self.random_offset
+    }
+
+    /// How much further along the rainbow each line starts than the last
+    pub(crate) fn line_step(&self) -> f64 {
+        match self.direction {
+            Direction::Lolcat => 1.0,
+            direction => direction.weights().1 * self.spread,
+        }
+    }
+
+    /// How much further along the rainbow each column is than the last
+    pub(crate) fn column_step(&self) -> f64 {
+        self.direction.weights().0 / self.spread
+    }
+
+    /// Check that the public fields still hold valid values
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError` if frequency or spread are not finite positive
+    /// numbers, the offset or direction angle is not finite, the tab stops
+    /// are not positive and increasing, or `block_lines` is 0
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if !self.frequency.is_finite() || self.frequency <= 0.0 {
+            return Err(ConfigError::InvalidFrequency(self.frequency));
+        }
+        if !self.spread.is_finite() || self.spread <= 0.0 {
+            return Err(ConfigError::InvalidSpread(self.spread));
+        }
+        if !self.random_offset.is_finite() {
+            return Err(ConfigError::InvalidOffset(self.random_offset));
+        }
+        if !self.tab_stops.is_valid() {
+            return Err(ConfigError::InvalidTabStops(self.tab_stops.clone()));
+        }
+        if let Direction::Angle(angle) = self.direction
+            && !angle.is_finite()
+        {
+            return Err(ConfigError::InvalidAngle(angle));
+        }
+        if self.block_lines == 0 {
+            return Err(ConfigError::InvalidBlockLines(self.block_lines));
+        }
+        Ok(())
     }
 }
 
@@ -89,8 +570,196 @@ impl Default for Config {
         Self {
             frequency: 0.1,
             spread: 8.0,
+            max_lines: None,
+            first_line: 0,
+            line_endings: LineEndings::Normalize,
+            carriage_returns: CarriageReturns::Character,
+            buffering: Buffering::Auto,
+            tab_stops: TabStops::Every(8),
+            tabs: Tabs::Expand,
+            decorations: Decorations::default(),
+            color_depth: ColorDepth::Auto,
+            layer: Layer::Foreground,
+            direction: Direction::Diagonal,
+            block_lines: DEFAULT_BLOCK_LINES,
+            fit: Fit::Off,
             random_offset: generate_random_offset(),
+            offset_set: false,
             force_color: false,
         }
     }
 }
+
+/// The serialized form of [`Config`]
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigRepr {
+    frequency: f64,
+    spread: f64,
+    #[serde(default)]
+    max_lines: Option<usize>,
+    #[serde(default)]
+    first_line: usize,
+    #[serde(default)]
+    line_endings: LineEndings,
+    #[serde(default)]
+    carriage_returns: CarriageReturns,
+    #[serde(default)]
+    buffering: Buffering,
+    #[serde(default)]
+    tab_stops: TabStops,
+    #[serde(default)]
+    tabs: Tabs,
+    #[serde(default)]
+    decorations: Decorations,
+    #[serde(default)]
+    color_depth: ColorDepth,
+    #[serde(default)]
+    layer: Layer,
+    #[serde(default)]
+    direction: Direction,
+    #[serde(default = "default_block_lines")]
+    block_lines: usize,
+    #[serde(default)]
+    fit: Fit,
+    /// `None` for a random offset, also when serializing one
+    #[serde(default)]
+    offset: Option<f64>,
+    #[serde(default)]
+    force_color: bool,
+}
+
+#[cfg(feature = "serde")]
+fn default_block_lines() -> usize {
+    DEFAULT_BLOCK_LINES
+}
+
+#[cfg(feature = "serde")]
+impl From<Config> for ConfigRepr {
+    fn from(config: Config) -> Self {
+        Self {
+            frequency: config.frequency,
+            spread: config.spread,
+            max_lines: config.max_lines,
+            first_line: config.first_line,
+            line_endings: config.line_endings,
+            carriage_returns: config.carriage_returns,
+            buffering: config.buffering,
+            tab_stops: config.tab_stops,
+            tabs: config.tabs,
+            decorations: config.decorations,
+            color_depth: config.color_depth,
+            layer: config.layer,
+            direction: config.direction,
+            block_lines: config.block_lines,
+            fit: config.fit,
+            offset: config.offset_set.then_some(config.random_offset),
+            force_color: config.force_color,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<ConfigRepr> for Config {
+    type Error = ConfigError;
+
+    fn try_from(repr: ConfigRepr) -> Result<Self, ConfigError> {
+        let mut config = Self::try_new(repr.frequency, repr.spread, repr.force_color)?;
+        config.max_lines = repr.max_lines;
+        config.first_line = repr.first_line;
+        config.line_endings = repr.line_endings;
+        config.carriage_returns = repr.carriage_returns;
+        config.buffering = repr.buffering;
+        config.tab_stops = repr.tab_stops;
+        config.tabs = repr.tabs;
+        config.decorations = repr.decorations;
+        config.color_depth = repr.color_depth;
+        config.layer = repr.layer;
+        config.direction = repr.direction;
+        config.block_lines = repr.block_lines;
+        config.fit = repr.fit;
+        if let Some(offset) = repr.offset {
+            config.set_offset(offset);
+        }
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serde_round_trip() {
+        let mut config = Config::try_new(0.2, 5.0, true).unwrap();
+        config.set_offset(12.5);
+        config.tab_stops = TabStops::List(vec![4, 8]);
+        config.decorations.line_numbers = LineNumbers::NonBlank;
+        config.color_depth = ColorDepth::Color256;
+
+        let json = serde_json::to_string(&config).unwrap();
+        let parsed: Config = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&parsed).unwrap(), json);
+        // Spelled like the command line's --color-depth
+        assert!(json.contains(r#""color_depth":"256""#), "{json}");
+        assert!((parsed.offset() - 12.5).abs() < f64::EPSILON);
+        assert!(parsed.force_color());
+        assert_eq!(parsed.tab_stops, TabStops::List(vec![4, 8]));
+        assert_eq!(parsed.decorations.line_numbers, LineNumbers::NonBlank);
+        assert_eq!(parsed.color_depth, ColorDepth::Color256);
+    }
+
+    #[test]
+    fn serde_fills_in_defaults_and_validates() {
+        let config: Config =
+            serde_json::from_str(r#"{"frequency": 0.1, "spread": 3, "layer": "background"}"#)
+                .unwrap();
+        assert_eq!(config.layer, Layer::Background);
+        assert_eq!(config.tab_stops, TabStops::Every(8));
+        assert!(!config.force_color());
+
+        let err =
+            serde_json::from_str::<Config>(r#"{"frequency": 0.1, "spread": -1}"#).unwrap_err();
+        assert!(err.to_string().contains("invalid spread: -1"), "{err}");
+        let err = serde_json::from_str::<Config>(
+            r#"{"frequency": 0.1, "spread": 1, "tab_stops": {"list": [8, 4]}}"#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("invalid tab stops"), "{err}");
+        assert!(serde_json::from_str::<Config>(r#"{"frequency": 0.1}"#).is_err());
+    }
+
+    #[test]
+    fn color_mode_names() {
+        assert_eq!(
+            serde_json::to_string(&ColorMode::TrueColor).unwrap(),
+            r#""truecolor""#
+        );
+        assert_eq!(
+            serde_json::from_str::<ColorMode>(r#""color256""#).unwrap(),
+            ColorMode::Color256
+        );
+        assert_eq!(
+            serde_json::from_str::<ColorDepth>(r#""truecolor""#).unwrap(),
+            ColorDepth::TrueColor
+        );
+        assert_eq!(
+            serde_json::from_str::<ColorDepth>(r#""color256""#).unwrap(),
+            ColorDepth::Color256
+        );
+    }
+
+    #[test]
+    fn random_offset_is_not_serialized() {
+        let config = Config::try_new(0.2, 5.0, false).unwrap();
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(json.contains(r#""offset":null"#), "{json}");
+
+        let mut config: Config = serde_json::from_str(&json).unwrap();
+        config.set_offset(3.0);
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(json.contains(r#""offset":3.0"#), "{json}");
+    }
+}
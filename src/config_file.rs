@@ -0,0 +1,250 @@
+//! Reading of the `lolcat-ultra` config file.
+//!
+//! The file is TOML with `key = value` pairs of string, number, boolean and
+//! array values, and `[profile.NAME]` tables holding the settings of a named
+//! profile. Keys are the long option names.
+
+use std::env;
+use std::fmt;
+use std::path::PathBuf;
+
+use toml::de::{DeTable, DeValue};
+
+/// A value in the config file
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Value {
+    Boolean(bool),
+    /// A number as written, for the option parser to interpret
+    Number(String),
+    String(String),
+    Array(Vec<Value>),
+}
+
+/// A `key = value` pair
+#[derive(Debug, PartialEq)]
+pub(crate) struct Setting {
+    /// 1-based line number, for error messages
+    pub(crate) line: usize,
+    pub(crate) key: String,
+    pub(crate) value: Value,
+}
+
+/// Top-level settings, which apply to every run, and named profiles
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct ConfigFile {
+    pub(crate) settings: Vec<Setting>,
+    pub(crate) profiles: Vec<(String, Vec<Setting>)>,
+}
+
+/// An error at a line of the config file
+#[derive(Debug, PartialEq)]
+pub(crate) struct Error {
+    pub(crate) line: usize,
+    pub(crate) message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Where the config file is looked for:
+/// `$XDG_CONFIG_HOME/lolcat-ultra/config.toml`, with `XDG_CONFIG_HOME`
+/// defaulting to `~/.config`
+pub(crate) fn path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            env::var_os("HOME")
+                .filter(|dir| !dir.is_empty())
+                .map(|home| PathBuf::from(home).join(".config"))
+        })?;
+    Some(config_home.join("lolcat-ultra").join("config.toml"))
+}
+
+impl ConfigFile {
+    /// The settings of `profile`, or `None` if the file has no such profile
+    pub(crate) fn profile(&self, profile: &str) -> Option<&[Setting]> {
+        self.profiles
+            .iter()
+            .find(|(name, _)| name == profile)
+            .map(|(_, settings)| settings.as_slice())
+    }
+}
+
+/// Parse the text of a config file
+pub(crate) fn parse(text: &str) -> Result<ConfigFile, Error> {
+    let line_at = |offset: usize| text[..offset].bytes().filter(|&b| b == b'\n').count() + 1;
+    let document = DeTable::parse(text).map_err(|err| Error {
+        line: err.span().map_or(1, |span| line_at(span.start)),
+        message: err.message().trim_end().to_string(),
+    })?;
+
+    let mut file = ConfigFile::default();
+    // Tables keep their keys sorted; the file order is what errors follow
+    let mut profiles = Vec::new();
+    for (key, value) in document.into_inner() {
+        let line = line_at(key.span().start);
+        let key = key.into_inner().into_owned();
+        match value.into_inner() {
+            DeValue::Table(table) if key == "profile" => {
+                for (name, settings) in table {
+                    let line = line_at(name.span().start);
+                    let name = name.into_inner().into_owned();
+                    let DeValue::Table(settings) = settings.into_inner() else {
+                        return Err(Error {
+                            line,
+                            message: format!("'profile.{name}' must be a [profile.{name}] table"),
+                        });
+                    };
+                    profiles.push((line, name, table_settings(settings, line_at)?));
+                }
+            }
+            DeValue::Table(_) => {
+                return Err(Error {
+                    line,
+                    message: format!("unknown section '{key}': expected [profile.NAME]"),
+                });
+            }
+            value => file.settings.push(Setting {
+                line,
+                value: setting_value(value).map_err(|message| Error { line, message })?,
+                key,
+            }),
+        }
+    }
+    file.settings.sort_by_key(|setting| setting.line);
+    profiles.sort_by_key(|&(line, ..)| line);
+    file.profiles = profiles
+        .into_iter()
+        .map(|(_, name, settings)| (name, settings))
+        .collect();
+    Ok(file)
+}
+
+/// The settings of a profile's table, in file order
+fn table_settings(
+    table: DeTable<'_>,
+    line_at: impl Fn(usize) -> usize,
+) -> Result<Vec<Setting>, Error> {
+    let mut settings = table
+        .into_iter()
+        .map(|(key, value)| {
+            let line = line_at(key.span().start);
+            let key = key.into_inner().into_owned();
+            let value = match value.into_inner() {
+                DeValue::Table(_) => Err(format!("unknown section '{key}'")),
+                value => setting_value(value),
+            };
+            value
+                .map(|value| Setting { line, key, value })
+                .map_err(|message| Error { line, message })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    settings.sort_by_key(|setting| setting.line);
+    Ok(settings)
+}
+
+/// A value as the option parser takes it, with numbers in decimal
+fn setting_value(value: DeValue<'_>) -> Result<Value, String> {
+    match value {
+        DeValue::Boolean(value) => Ok(Value::Boolean(value)),
+        DeValue::String(value) => Ok(Value::String(value.into_owned())),
+        DeValue::Integer(value) => i64::from_str_radix(value.as_str(), value.radix())
+            .map(|value| Value::Number(value.to_string()))
+            .map_err(|_| format!("invalid value '{}': number too large", value.as_str())),
+        DeValue::Float(value) => Ok(Value::Number(value.as_str().to_string())),
+        DeValue::Array(values) => values
+            .into_iter()
+            .map(|value| match value.into_inner() {
+                DeValue::Array(_) | DeValue::Table(_) => {
+                    Err("arrays may only hold strings and numbers".to_string())
+                }
+                value => setting_value(value),
+            })
+            .collect::<Result<_, _>>()
+            .map(Value::Array),
+        DeValue::Datetime(value) => Err(format!("invalid value '{value}': dates are not taken")),
+        DeValue::Table(_) => Err("tables are not taken as values".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setting(line: usize, key: &str, value: Value) -> Setting {
+        Setting {
+            line,
+            key: key.to_string(),
+            value,
+        }
+    }
+
+    #[test]
+    fn parses_settings_and_profiles() {
+        let file = parse(
+            "# defaults\n\
+             frequency = 0.1\n\
+             \"keep-tabs\" = true # comment\n\
+             \n\
+             [profile.logs]\n\
+             line-endings = 'normalize'\n\
+             tab-stops = [0x4, 1_000]\n\
+             head = \"10\\u0030\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            file.settings,
+            [
+                setting(2, "frequency", Value::Number("0.1".to_string())),
+                setting(3, "keep-tabs", Value::Boolean(true)),
+            ]
+        );
+        assert_eq!(
+            file.profile("logs").unwrap(),
+            [
+                setting(6, "line-endings", Value::String("normalize".to_string())),
+                setting(
+                    7,
+                    "tab-stops",
+                    Value::Array(vec![
+                        Value::Number("4".to_string()),
+                        Value::Number("1000".to_string()),
+                    ])
+                ),
+                setting(8, "head", Value::String("100".to_string())),
+            ]
+        );
+        assert!(file.profile("other").is_none());
+    }
+
+    #[test]
+    fn errors_point_to_the_line() {
+        for (text, line, message) in [
+            ("frequency 0.1", 1, "key with no value, expected `=`"),
+            (
+                "\n[colors]",
+                2,
+                "unknown section 'colors': expected [profile.NAME]",
+            ),
+            ("a = 1\na = 2", 2, "duplicate key"),
+            ("a = \"open", 1, "invalid basic string, expected `\"`"),
+            ("a = [1, 2", 1, "unclosed array, expected `]`"),
+            ("[profile.x]\n[profile.x]", 2, "duplicate key"),
+            ("\n[profile.x.y]", 2, "unknown section 'y'"),
+            (
+                "a = 1\nb = 1979-05-27",
+                2,
+                "invalid value '1979-05-27': dates are not taken",
+            ),
+            ("a = [[1]]", 1, "arrays may only hold strings and numbers"),
+        ] {
+            let err = parse(text).unwrap_err();
+            assert_eq!(err.line, line, "{text:?}");
+            assert_eq!(err.message, message, "{text:?}");
+        }
+    }
+}
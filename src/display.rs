@@ -0,0 +1,113 @@
+use std::fmt;
+use std::io::Write;
+
+use crate::color::ColorMode;
+use crate::config::Config;
+use crate::writer::RainbowWriter;
+
+/// A `Display` wrapper that formats its value with rainbow colors.
+///
+/// The wrapped value is formatted first and the resulting text is colored
+/// exactly as `process_input` would color it, followed by the terminal
+/// reset sequence. With `ColorMode::NoColor` the value is formatted as is.
+///
+/// ```
+/// use lolcat_ultra::{Rainbow, RainbowExt};
+///
+/// let banner = format!("{}", Rainbow::new("hello"));
+/// println!("{banner} {}", "world".rainbow());
+/// ```
+pub struct Rainbow<T> {
+    value: T,
+    config: Config,
+    color_mode: ColorMode,
+}
+
+impl<T: fmt::Display> Rainbow<T> {
+    /// Wrap a value using the default `Config` and the color mode detected
+    /// for stdout
+    #[must_use]
+    pub fn new(value: T) -> Self {
+        Self::with_config(value, &Config::default())
+    }
+
+    /// Wrap a value using the given `Config` and the color mode detected for
+    /// stdout
+    #[must_use]
+    pub fn with_config(value: T, config: &Config) -> Self {
+        Self {
+            value,
+            config: config.clone(),
+            color_mode: config.color_mode_for(&std::io::stdout()),
+        }
+    }
+
+    /// Override the detected color mode
+    #[must_use]
+    pub fn color_mode(mut self, color_mode: ColorMode) -> Self {
+        self.color_mode = color_mode;
+        self
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Rainbow<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.color_mode == ColorMode::NoColor {
+            return self.value.fmt(f);
+        }
+
+        let text = self.value.to_string();
+        let mut writer = RainbowWriter::with_color_mode(
+            Vec::with_capacity(text.len() * 20),
+            &self.config,
+            self.color_mode,
+        );
+        writer.write_all(text.as_bytes()).map_err(|_| fmt::Error)?;
+        let colored = writer.finish().map_err(|_| fmt::Error)?;
+        // Only ASCII escape sequences are inserted between codepoints
+        let colored = String::from_utf8(colored).map_err(|_| fmt::Error)?;
+        f.write_str(&colored)
+    }
+}
+
+/// Extension trait adding `.rainbow()` to every `Display` type
+pub trait RainbowExt: fmt::Display {
+    /// Wrap `self` in a [`Rainbow`] using the default `Config`
+    fn rainbow(&self) -> Rainbow<&Self> {
+        Rainbow::new(self)
+    }
+
+    /// Wrap `self` in a [`Rainbow`] using the given `Config`
+    fn rainbow_with(&self, config: &Config) -> Rainbow<&Self> {
+        Rainbow::with_config(self, config)
+    }
+}
+
+impl<T: fmt::Display + ?Sized> RainbowExt for T {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_rainbow_writer() {
+        let config = Config::try_new(0.04, 4.0, true).unwrap();
+        let msg = "multi\nline \u{1F308} message";
+
+        let mut writer = RainbowWriter::with_color_mode(Vec::new(), &config, ColorMode::TrueColor);
+        writer.write_all(msg.as_bytes()).unwrap();
+        let expected = String::from_utf8(writer.finish().unwrap()).unwrap();
+
+        let formatted = format!(
+            "{}",
+            msg.rainbow_with(&config).color_mode(ColorMode::TrueColor)
+        );
+        assert_eq!(formatted, expected);
+    }
+
+    #[test]
+    fn no_color_formats_plainly() {
+        let formatted = format!("{}", Rainbow::new(42).color_mode(ColorMode::NoColor));
+        assert_eq!(formatted, "42");
+    }
+}
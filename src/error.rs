@@ -0,0 +1,63 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::config::ConfigError;
+
+/// Errors returned by the input processing functions
+#[derive(Debug)]
+pub enum ProcessError {
+    /// Reading from the input failed
+    Read(io::Error),
+    /// Writing to the output failed
+    Write(io::Error),
+    /// The output was closed before all input was written (e.g. `| head`)
+    BrokenPipe,
+    /// Output was truncated after reaching the line limit
+    LineLimit(usize),
+    /// The configuration is invalid
+    InvalidConfig(ConfigError),
+    /// Processing was stopped by the signal with this number, handled after
+    /// [`handle_interrupts`](crate::handle_interrupts)
+    Interrupted(i32),
+}
+
+impl ProcessError {
+    /// Classify an error returned while writing output
+    pub(crate) fn write(err: io::Error) -> Self {
+        if err.kind() == io::ErrorKind::BrokenPipe {
+            Self::BrokenPipe
+        } else {
+            Self::Write(err)
+        }
+    }
+}
+
+impl fmt::Display for ProcessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read(_) => write!(f, "failed to read input"),
+            Self::Write(_) => write!(f, "failed to write output"),
+            Self::BrokenPipe => write!(f, "broken pipe"),
+            Self::LineLimit(limit) => write!(f, "output truncated after {limit} lines"),
+            Self::InvalidConfig(_) => write!(f, "invalid configuration"),
+            Self::Interrupted(signal) => write!(f, "interrupted by signal {signal}"),
+        }
+    }
+}
+
+impl Error for ProcessError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Read(err) | Self::Write(err) => Some(err),
+            Self::InvalidConfig(err) => Some(err),
+            Self::BrokenPipe | Self::LineLimit(_) | Self::Interrupted(_) => None,
+        }
+    }
+}
+
+impl From<ConfigError> for ProcessError {
+    fn from(err: ConfigError) -> Self {
+        Self::InvalidConfig(err)
+    }
+}
@@ -0,0 +1,368 @@
+//! Shell completion scripts and the man page, generated from the option
+//! tables in [`crate::cli`] so they list the same options as `--help`.
+
+use std::fmt::Write;
+use std::str::FromStr;
+
+use crate::cli::{ABOUT, Action, COMPAT_OPTIONS, NATIVE_OPTIONS, OptionSpec};
+
+const BIN: &str = "lolcat-ultra";
+
+/// A shell to generate completions for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Elvish,
+    Powershell,
+}
+
+impl FromStr for Shell {
+    type Err = String;
+
+    fn from_str(shell: &str) -> Result<Self, String> {
+        match shell {
+            "bash" => Ok(Self::Bash),
+            "zsh" => Ok(Self::Zsh),
+            "fish" => Ok(Self::Fish),
+            "elvish" => Ok(Self::Elvish),
+            "powershell" => Ok(Self::Powershell),
+            _ => Err(format!(
+                "invalid shell '{shell}': expected bash, zsh, fish, elvish or powershell"
+            )),
+        }
+    }
+}
+
+/// The options to complete, leaving out hidden ones
+fn options() -> impl Iterator<Item = &'static OptionSpec> {
+    NATIVE_OPTIONS.iter().filter(|option| !option.hidden)
+}
+
+/// The `-x` and `--long` spellings of `option`
+fn names(option: &OptionSpec) -> Vec<String> {
+    let short = option.short.map(|short| format!("-{short}"));
+    let long = option.long.map(|long| format!("--{long}"));
+    short.into_iter().chain(long).collect()
+}
+
+fn takes_value(option: &OptionSpec) -> bool {
+    matches!(option.action, Action::Value { .. })
+}
+
+/// Quote `text` for a shell in single quotes, where a quote is written as
+/// `quote_escape`
+fn single_quoted(text: &str, quote_escape: &str) -> String {
+    format!("'{}'", text.replace('\'', quote_escape))
+}
+
+/// A completion script for `shell`
+pub(crate) fn completions(shell: Shell) -> String {
+    match shell {
+        Shell::Bash => bash(),
+        Shell::Zsh => zsh(),
+        Shell::Fish => fish(),
+        Shell::Elvish => elvish(),
+        Shell::Powershell => powershell(),
+    }
+}
+
+fn bash() -> String {
+    let function = format!("_{}", BIN.replace('-', "_"));
+    let words: Vec<String> = options().flat_map(names).collect();
+    let mut script = format!(
+        "{function}() {{\n\
+        \x20   local cur=\"${{COMP_WORDS[COMP_CWORD]}}\"\n\
+        \x20   local prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"\n\
+        \x20   case \"$prev\" in\n"
+    );
+    for option in options().filter(|option| takes_value(option)) {
+        let choices = option.choices();
+        let reply = if choices.is_empty() {
+            // Nothing sensible to suggest for a number or a name
+            "COMPREPLY=()".to_string()
+        } else {
+            format!(
+                "COMPREPLY=($(compgen -W {} -- \"$cur\"))",
+                single_quoted(&choices.join(" "), "'\\''")
+            )
+        };
+        let _ = writeln!(
+            script,
+            "        {})\n            {reply}\n            return 0\n            ;;",
+            names(option).join("|")
+        );
+    }
+    let _ = write!(
+        script,
+        "    esac\n\
+        \x20   if [[ \"$cur\" == -* ]]; then\n\
+        \x20       COMPREPLY=($(compgen -W {} -- \"$cur\"))\n\
+        \x20   else\n\
+        \x20       COMPREPLY=($(compgen -f -- \"$cur\"))\n\
+        \x20   fi\n\
+        }}\n\
+        \n\
+        complete -o filenames -F {function} {BIN}\n",
+        single_quoted(&words.join(" "), "'\\''")
+    );
+    script
+}
+
+fn zsh() -> String {
+    let mut script = format!("#compdef {BIN}\n\n_arguments -s -S \\\n");
+    for option in options() {
+        // Brackets end the description, and `:` separates the value spec
+        let help = option
+            .help
+            .replace('[', "\\[")
+            .replace(']', "\\]")
+            .replace(':', "\\:");
+        let value = match option.action {
+            Action::Value { name, .. } => {
+                let choices = option.choices();
+                if choices.is_empty() {
+                    format!(":{name}: ")
+                } else {
+                    format!(":{name}:({})", choices.join(" "))
+                }
+            }
+            Action::Flag(_) => String::new(),
+        };
+        let spec = match (option.short, option.long) {
+            (Some(short), Some(long)) => {
+                let (short_suffix, long_suffix) = if takes_value(option) {
+                    ("+", "=")
+                } else {
+                    ("", "")
+                };
+                format!(
+                    "'(-{short} --{long})'{{-{short}{short_suffix},--{long}{long_suffix}}}{}",
+                    single_quoted(&format!("[{help}]{value}"), "'\\''")
+                )
+            }
+            (None, Some(long)) => {
+                let suffix = if takes_value(option) { "=" } else { "" };
+                single_quoted(&format!("--{long}{suffix}[{help}]{value}"), "'\\''")
+            }
+            (Some(short), None) => single_quoted(&format!("-{short}[{help}]{value}"), "'\\''"),
+            (None, None) => continue,
+        };
+        let _ = writeln!(script, "  {spec} \\");
+    }
+    script.push_str("  '*:file:_files'\n");
+    script
+}
+
+fn fish() -> String {
+    let mut script = String::new();
+    for option in options() {
+        let _ = write!(script, "complete -c {BIN}");
+        if let Some(short) = option.short {
+            let _ = write!(script, " -s {short}");
+        }
+        if let Some(long) = option.long {
+            let _ = write!(script, " -l {long}");
+        }
+        if takes_value(option) {
+            let choices = option.choices();
+            if choices.is_empty() {
+                script.push_str(" -r");
+            } else {
+                let _ = write!(
+                    script,
+                    " -x -a {}",
+                    single_quoted(&choices.join(" "), "\\'")
+                );
+            }
+        }
+        let _ = writeln!(script, " -d {}", single_quoted(option.help, "\\'"));
+    }
+    script
+}
+
+fn elvish() -> String {
+    let mut script = format!(
+        "use str\n\
+        \n\
+        set edit:completion:arg-completer[{BIN}] = {{|@words|\n\
+        \x20   var current = $words[-1]\n\
+        \x20   if (str:has-prefix $current -) {{\n"
+    );
+    for option in options() {
+        for name in names(option) {
+            let _ = writeln!(
+                script,
+                "        edit:complex-candidate {name} &display={}",
+                single_quoted(&format!("{name}  {}", option.help), "''")
+            );
+        }
+    }
+    script.push_str(
+        "    } else {\n\
+        \x20       edit:complete-filename $current\n\
+        \x20   }\n\
+        }\n",
+    );
+    script
+}
+
+fn powershell() -> String {
+    let mut script = format!(
+        "using namespace System.Management.Automation\n\
+        \n\
+        Register-ArgumentCompleter -Native -CommandName '{BIN}' -ScriptBlock {{\n\
+        \x20   param($wordToComplete, $commandAst, $cursorPosition)\n\
+        \x20   @(\n"
+    );
+    for option in options() {
+        for name in names(option) {
+            let _ = writeln!(
+                script,
+                "        [CompletionResult]::new('{name}', '{name}', \
+                 [CompletionResultType]::ParameterName, {})",
+                single_quoted(option.help, "''")
+            );
+        }
+    }
+    script.push_str(
+        "    ) | Where-Object { $_.CompletionText -like \"$wordToComplete*\" }\n\
+        }\n",
+    );
+    script
+}
+
+/// Escape `text` for roff
+fn roff(text: &str) -> String {
+    let escaped = text.replace('\\', "\\e").replace('-', "\\-");
+    // A line starting with these would be taken as a request
+    if escaped.starts_with(['.', '\'']) {
+        format!("\\&{escaped}")
+    } else {
+        escaped
+    }
+}
+
+/// `option` as a roff paragraph tag, e.g. `\fB\-f\fR, \fB\-\-frequency\fR \fIFREQUENCY\fR`
+fn roff_tag(option: &OptionSpec) -> String {
+    let mut tag = names(option)
+        .iter()
+        .map(|name| format!("\\fB{}\\fR", roff(name)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    if let Action::Value { name, .. } = option.action {
+        let _ = write!(tag, " \\fI{name}\\fR");
+    }
+    tag
+}
+
+fn push_options(page: &mut String, options: &[OptionSpec]) {
+    for option in options.iter().filter(|option| !option.hidden) {
+        let _ = write!(page, ".TP\n{}\n{}\n", roff_tag(option), roff(option.help));
+    }
+}
+
+/// A man page in roff, for section 1
+pub(crate) fn man(version: &str) -> String {
+    let mut page = format!(
+        ".TH {} 1 \"\" \"{BIN} {version}\" \"User Commands\"\n\
+        .SH NAME\n\
+        {} \\- {ABOUT}\n\
+        .SH SYNOPSIS\n\
+        .B {}\n\
+        [\\fIOPTIONS\\fR] [\\fB\\-\\-\\fR] [\\fIFILE\\fR]...\n\
+        .SH DESCRIPTION\n\
+        Concatenate the \\fIFILE\\fRs, or standard input, to standard output \
+        in rainbow colors. With no \\fIFILE\\fR, or when \\fIFILE\\fR is \\-, \
+        read standard input. Colors are only written to a terminal unless \
+        \\fB\\-\\-force\\fR is given.\n\
+        .SH OPTIONS\n",
+        roff(&BIN.to_uppercase()),
+        roff(BIN),
+        roff(BIN),
+    );
+    push_options(&mut page, NATIVE_OPTIONS);
+    page.push_str(
+        ".SH COMPATIBILITY\n\
+        When run as \\fBlolcat\\fR, or with \\fB\\-\\-compat\\fR, the options \
+        of the original lolcat are taken instead, and the config file and \
+        \\fBLOLCAT_OPTS\\fR are ignored:\n",
+    );
+    push_options(&mut page, COMPAT_OPTIONS);
+    page.push_str(
+        ".PP\n\
+        When run as \\fBcat\\fR, or with \\fB\\-\\-cat\\fR, \\fB\\-s\\fR and \
+        \\fB\\-v\\fR are taken as \\fBcat\\fR takes them, for \
+        \\fB\\-\\-squeeze\\-blank\\fR and \\fB\\-\\-show\\-nonprinting\\fR, \
+        rather than for \\fB\\-\\-spread\\fR and \\fB\\-\\-version\\fR.\n\
+        .SH ENVIRONMENT\n\
+        .TP\n\
+        .B LOLCAT_OPTS\n\
+        Options read before those on the command line, split into words like \
+        a shell would. Only the options the config file can set are taken.\n\
+        .TP\n\
+        .B NO_COLOR\n\
+        If set, output is not colored unless \\fB\\-\\-force\\fR is given.\n\
+        .SH FILES\n\
+        .TP\n\
+        .I $XDG_CONFIG_HOME/lolcat\\-ultra/config.toml\n\
+        Defaults for the long options, as \\fIname\\fR = \\fIvalue\\fR with \
+        \\fBtrue\\fR for flags, which cannot be unset, and named profiles in \\fB[profile.\\fR\\fINAME\\fR\\fB]\\fR \
+        tables selected with \\fB\\-\\-profile\\fR. \\fBXDG_CONFIG_HOME\\fR \
+        defaults to \\fI~/.config\\fR.\n\
+        .SH EXIT STATUS\n\
+        0 on success, 64 for invalid options, 65 when \\fB\\-\\-max\\-lines\\fR \
+        truncated the output, 66 when an input could not be opened and 74 \
+        when an input could not be read or the output could not be written.\n",
+    );
+    page
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn completions_list_every_visible_option() {
+        for shell in ["bash", "zsh", "fish", "elvish", "powershell"] {
+            let script = completions(shell.parse().unwrap());
+            for option in NATIVE_OPTIONS {
+                // fish takes the names without dashes
+                let fish_names = [
+                    option.short.map(|short| format!("-s {short}")),
+                    option.long.map(|long| format!("-l {long}")),
+                ];
+                let names = if shell == "fish" {
+                    fish_names.into_iter().flatten().collect()
+                } else {
+                    names(option)
+                };
+                for name in names {
+                    assert_eq!(script.contains(&name), !option.hidden, "{shell}: {name}");
+                }
+            }
+        }
+        assert!(completions(Shell::Bash).contains("compgen -W 'preserve normalize'"));
+        assert_eq!(
+            "tcsh".parse::<Shell>().unwrap_err(),
+            "invalid shell 'tcsh': expected bash, zsh, fish, elvish or powershell"
+        );
+    }
+
+    #[test]
+    fn man_page_lists_every_visible_option() {
+        let page = man("1.2.3");
+        assert!(page.starts_with(".TH LOLCAT\\-ULTRA 1 \"\" \"lolcat-ultra 1.2.3\""));
+        for option in NATIVE_OPTIONS.iter().chain(COMPAT_OPTIONS) {
+            assert_eq!(
+                page.contains(&roff_tag(option)),
+                !option.hidden,
+                "{}",
+                option.synopsis()
+            );
+        }
+        // Requests only where intended
+        assert!(page.lines().all(|line| !line.starts_with('\'')));
+    }
+}
@@ -1,12 +1,30 @@
 mod ansi;
+mod block;
 pub mod color;
 mod config;
+mod display;
+mod error;
+#[cfg(any(feature = "tracing", feature = "log"))]
+pub mod logging;
 mod processor;
 pub mod rainbow;
+mod spans;
 mod terminal;
+#[cfg(any(feature = "ratatui", feature = "crossterm"))]
+mod tui;
+mod writer;
 
 // Re-export public API
 pub use color::ColorMode;
-pub use config::{Config, ConfigError};
+pub use config::{
+    Buffering, CarriageReturns, ColorDepth, Config, ConfigError, Decorations, Direction, Fit,
+    Layer, LineEndings, LineNumbers, TabStops, Tabs,
+};
+pub use display::{Rainbow, RainbowExt};
+pub use error::ProcessError;
+#[cfg(feature = "macros")]
+pub use lolcat_ultra_macros::{rainbow, rainbow_256};
 pub use processor::{process_input, process_input_to_writer, process_input_with_color_mode};
-pub use terminal::setup_terminal_cleanup;
+pub use spans::Spans;
+pub use terminal::{InterruptGuard, handle_interrupts, setup_terminal_cleanup};
+pub use writer::RainbowWriter;
@@ -0,0 +1,465 @@
+//! Rainbow-colored log output.
+//!
+//! With the `tracing` feature this provides a `tracing-subscriber` event
+//! formatter and field formatter; with the `log` feature a `log` logger.
+//! Each emitted event continues the rainbow from the previous one, as if
+//! the events were consecutive lines of one input.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::color::ColorMode;
+use crate::config::{Config, LineEndings};
+use crate::display::Rainbow;
+
+/// Which part of each log line is colored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorScope {
+    /// Color the whole line, including level, target and fields
+    #[default]
+    Line,
+    /// Color only the log message
+    Message,
+}
+
+/// Colors successive events, continuing the phase across them
+struct EventColorizer {
+    config: Config,
+    color_mode: ColorMode,
+    lines: AtomicUsize,
+}
+
+impl EventColorizer {
+    fn new(config: &Config, color_mode: ColorMode) -> Self {
+        Self {
+            config: config.clone(),
+            color_mode,
+            lines: AtomicUsize::new(0),
+        }
+    }
+
+    /// Color `text` as the next line(s) of the stream
+    fn colorize(&self, text: &str) -> String {
+        if self.color_mode == ColorMode::NoColor {
+            return text.to_string();
+        }
+
+        let mut line_count = text.matches('\n').count();
+        if !text.ends_with('\n') {
+            line_count += 1;
+        }
+        let first_line = self.lines.fetch_add(line_count, Ordering::Relaxed);
+
+        // Starting at the next line continues the rainbow where the previous
+        // event left off
+        let mut config = self.config.clone();
+        config.first_line += first_line;
+        // The reset goes before the final line ending, so nothing is left
+        // colored at the start of the next line
+        let (text, line_end) = match text.strip_suffix('\n') {
+            Some(text) => match text.strip_suffix('\r') {
+                Some(text) if config.line_endings == LineEndings::Preserve => (text, "\r\n"),
+                Some(text) => (text, "\n"),
+                None => (text, "\n"),
+            },
+            None => (text, ""),
+        };
+        let mut colored = Rainbow::with_config(text, &config)
+            .color_mode(self.color_mode)
+            .to_string();
+        colored.push_str(line_end);
+        colored
+    }
+}
+
+#[cfg(feature = "tracing")]
+pub use self::tracing_format::{RainbowFields, RainbowFormat, rainbow_layer};
+
+#[cfg(feature = "tracing")]
+mod tracing_format {
+    use std::fmt::{self, Write as _};
+    use std::sync::Arc;
+
+    use tracing_core::field::{Field, Visit};
+    use tracing_core::{Event, Subscriber};
+    use tracing_subscriber::field::RecordFields;
+    use tracing_subscriber::fmt::format::{Format, Writer};
+    use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+    use tracing_subscriber::registry::LookupSpan;
+    use tracing_subscriber::{Layer, fmt as tracing_fmt};
+
+    use super::{ColorScope, EventColorizer};
+    use crate::color::ColorMode;
+    use crate::config::Config;
+
+    /// Build a `tracing-subscriber` fmt layer writing rainbow-colored events
+    /// to stdout
+    #[must_use]
+    pub fn rainbow_layer<S>(config: &Config, scope: ColorScope) -> Box<dyn Layer<S> + Send + Sync>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        match scope {
+            ColorScope::Line => tracing_fmt::layer()
+                .event_format(RainbowFormat::new(config))
+                .boxed(),
+            ColorScope::Message => tracing_fmt::layer()
+                .fmt_fields(RainbowFields::new(config))
+                .boxed(),
+        }
+    }
+
+    /// Event formatter that colors each whole formatted line.
+    ///
+    /// Wraps another event formatter (the default full format unless given
+    /// one with [`RainbowFormat::with_inner`]).
+    pub struct RainbowFormat<F = Format> {
+        inner: F,
+        colorizer: Arc<EventColorizer>,
+    }
+
+    impl RainbowFormat {
+        /// Color the default full format, using the color mode detected for
+        /// stdout
+        #[must_use]
+        pub fn new(config: &Config) -> Self {
+            Self::with_inner(Format::default(), config)
+        }
+    }
+
+    impl<F> RainbowFormat<F> {
+        /// Color the output of another event formatter
+        #[must_use]
+        pub fn with_inner(inner: F, config: &Config) -> Self {
+            let color_mode = config.color_mode_for(&std::io::stdout());
+            Self {
+                inner,
+                colorizer: Arc::new(EventColorizer::new(config, color_mode)),
+            }
+        }
+
+        /// Override the detected color mode
+        #[must_use]
+        pub fn color_mode(self, color_mode: ColorMode) -> Self {
+            let colorizer = EventColorizer::new(&self.colorizer.config, color_mode);
+            Self {
+                inner: self.inner,
+                colorizer: Arc::new(colorizer),
+            }
+        }
+    }
+
+    impl<S, N, F> FormatEvent<S, N> for RainbowFormat<F>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+        N: for<'a> FormatFields<'a> + 'static,
+        F: FormatEvent<S, N>,
+    {
+        fn format_event(
+            &self,
+            ctx: &FmtContext<'_, S, N>,
+            mut writer: Writer<'_>,
+            event: &Event<'_>,
+        ) -> fmt::Result {
+            let mut line = String::new();
+            self.inner
+                .format_event(ctx, Writer::new(&mut line), event)?;
+            writer.write_str(&self.colorizer.colorize(&line))
+        }
+    }
+
+    /// Field formatter that colors only the `message` field.
+    ///
+    /// Other fields are written as `name=value` pairs, like the default
+    /// field formatter.
+    pub struct RainbowFields {
+        colorizer: EventColorizer,
+    }
+
+    impl RainbowFields {
+        /// Create a field formatter using the color mode detected for stdout
+        #[must_use]
+        pub fn new(config: &Config) -> Self {
+            let color_mode = config.color_mode_for(&std::io::stdout());
+            Self {
+                colorizer: EventColorizer::new(config, color_mode),
+            }
+        }
+
+        /// Override the detected color mode
+        #[must_use]
+        pub fn color_mode(self, color_mode: ColorMode) -> Self {
+            Self {
+                colorizer: EventColorizer::new(&self.colorizer.config, color_mode),
+            }
+        }
+    }
+
+    impl<'writer> FormatFields<'writer> for RainbowFields {
+        fn format_fields<R: RecordFields>(
+            &self,
+            mut writer: Writer<'writer>,
+            fields: R,
+        ) -> fmt::Result {
+            let mut visitor = FieldVisitor::default();
+            fields.record(&mut visitor);
+
+            if let Some(message) = visitor.message {
+                writer.write_str(&self.colorizer.colorize(&message))?;
+                if !visitor.fields.is_empty() {
+                    writer.write_char(' ')?;
+                }
+            }
+            writer.write_str(&visitor.fields)
+        }
+    }
+
+    #[derive(Default)]
+    struct FieldVisitor {
+        message: Option<String>,
+        fields: String,
+    }
+
+    impl Visit for FieldVisitor {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            if field.name() == "message" {
+                self.message = Some(value.to_string());
+            } else {
+                self.record_debug(field, &value);
+            }
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            let name = field.name();
+            if name == "message" {
+                self.message = Some(format!("{value:?}"));
+                return;
+            }
+            // Skip metadata already handled by the event formatter
+            if name.starts_with("log.") {
+                return;
+            }
+            if !self.fields.is_empty() {
+                self.fields.push(' ');
+            }
+            let name = name.strip_prefix("r#").unwrap_or(name);
+            // Writing to a `String` cannot fail
+            let _ = write!(self.fields, "{name}={value:?}");
+        }
+    }
+}
+
+#[cfg(feature = "log")]
+pub use self::log_logger::RainbowLogger;
+
+#[cfg(feature = "log")]
+mod log_logger {
+    use std::io::Write;
+
+    use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+    use super::{ColorScope, EventColorizer};
+    use crate::color::ColorMode;
+    use crate::config::Config;
+
+    /// A `log` logger writing rainbow-colored records to stderr.
+    ///
+    /// Records are formatted as `LEVEL target: message`.
+    pub struct RainbowLogger {
+        colorizer: EventColorizer,
+        scope: ColorScope,
+        level: LevelFilter,
+    }
+
+    impl RainbowLogger {
+        /// Create a logger for all levels, coloring whole lines, using the
+        /// color mode detected for stderr
+        #[must_use]
+        pub fn new(config: &Config) -> Self {
+            let color_mode = config.color_mode_for(&std::io::stderr());
+            Self {
+                colorizer: EventColorizer::new(config, color_mode),
+                scope: ColorScope::Line,
+                level: LevelFilter::Trace,
+            }
+        }
+
+        /// Choose which part of each record is colored
+        #[must_use]
+        pub fn scope(mut self, scope: ColorScope) -> Self {
+            self.scope = scope;
+            self
+        }
+
+        /// Set the most verbose level that is logged
+        #[must_use]
+        pub fn level(mut self, level: LevelFilter) -> Self {
+            self.level = level;
+            self
+        }
+
+        /// Override the detected color mode
+        #[must_use]
+        pub fn color_mode(mut self, color_mode: ColorMode) -> Self {
+            self.colorizer = EventColorizer::new(&self.colorizer.config, color_mode);
+            self
+        }
+
+        /// Install this logger as the global `log` logger
+        ///
+        /// # Errors
+        ///
+        /// Returns an error if a global logger has already been set
+        pub fn init(self) -> Result<(), SetLoggerError> {
+            let level = self.level;
+            log::set_boxed_logger(Box::new(self))?;
+            log::set_max_level(level);
+            Ok(())
+        }
+
+        /// Format and color one record, including the trailing newline
+        fn format_record(&self, record: &Record<'_>) -> String {
+            let prefix = format!("{:<5} {}: ", record.level(), record.target());
+            match self.scope {
+                ColorScope::Line => {
+                    let line = format!("{prefix}{}\n", record.args());
+                    self.colorizer.colorize(&line)
+                }
+                ColorScope::Message => {
+                    let message = self.colorizer.colorize(&record.args().to_string());
+                    format!("{prefix}{message}\n")
+                }
+            }
+        }
+    }
+
+    impl Log for RainbowLogger {
+        fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+            metadata.level() <= self.level
+        }
+
+        fn log(&self, record: &Record<'_>) {
+            if !self.enabled(record.metadata()) {
+                return;
+            }
+            let line = self.format_record(record);
+            let _ = std::io::stderr().lock().write_all(line.as_bytes());
+        }
+
+        fn flush(&self) {
+            let _ = std::io::stderr().flush();
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn record_line(logger: &RainbowLogger, message: &str) -> String {
+            logger.format_record(
+                &Record::builder()
+                    .args(format_args!("{message}"))
+                    .level(log::Level::Info)
+                    .target("app")
+                    .build(),
+            )
+        }
+
+        #[test]
+        fn message_scope_leaves_prefix_plain() {
+            let config = Config::try_new(0.1, 3.0, true).unwrap();
+            let logger = RainbowLogger::new(&config)
+                .color_mode(ColorMode::TrueColor)
+                .scope(ColorScope::Message);
+            let line = record_line(&logger, "hello");
+            assert!(line.starts_with("INFO  app: \x1b[38;2;"));
+            assert!(line.ends_with("\x1b[0m\x1b[39m\x1b[49m\n"));
+        }
+
+        #[test]
+        fn line_scope_resets_before_the_newline() {
+            let config = Config::try_new(0.1, 3.0, true).unwrap();
+            let logger = RainbowLogger::new(&config).color_mode(ColorMode::TrueColor);
+            let line = record_line(&logger, "hello");
+            assert!(line.starts_with("\x1b[38;2;"));
+            assert!(line.ends_with("o\x1b[0m\x1b[39m\x1b[49m\n"));
+        }
+
+        #[test]
+        fn phase_continues_across_records() {
+            let config = Config::try_new(0.1, 3.0, true).unwrap();
+            let logger = RainbowLogger::new(&config).color_mode(ColorMode::TrueColor);
+            let first = record_line(&logger, "same");
+            let second = record_line(&logger, "same");
+            assert_ne!(first, second);
+
+            let mut shifted = config.clone();
+            shifted.first_line += 1;
+            let restarted = RainbowLogger::new(&shifted).color_mode(ColorMode::TrueColor);
+            assert_eq!(second, record_line(&restarted, "same"));
+        }
+    }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::fmt::MakeWriter;
+
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Captured {
+        type Writer = Self;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    fn capture(format_message: bool) -> String {
+        let config = Config::try_new(0.1, 3.0, true).unwrap();
+        let captured = Captured::default();
+        let builder = tracing_subscriber::fmt()
+            .without_time()
+            .with_writer(captured.clone());
+        let dispatch = if format_message {
+            let fields = RainbowFields::new(&config).color_mode(ColorMode::TrueColor);
+            tracing_core::Dispatch::new(builder.fmt_fields(fields).finish())
+        } else {
+            let format = RainbowFormat::new(&config).color_mode(ColorMode::TrueColor);
+            tracing_core::Dispatch::new(builder.event_format(format).finish())
+        };
+        tracing_core::dispatcher::with_default(&dispatch, || {
+            tracing::info!(answer = 42, "first");
+            tracing::info!("second");
+        });
+        String::from_utf8(captured.0.lock().unwrap().clone()).unwrap()
+    }
+
+    #[test]
+    fn formats_whole_lines() {
+        let output = capture(false);
+        assert!(output.starts_with("\x1b[38;2;"));
+        assert_eq!(output.matches("\x1b[0m\x1b[39m\x1b[49m\n").count(), 2);
+    }
+
+    #[test]
+    fn formats_only_messages() {
+        let output = capture(true);
+        assert!(output.contains("\x1b[0m\x1b[39m\x1b[49m answer=42"));
+        assert!(!output.starts_with('\x1b'));
+    }
+}
@@ -1,14 +1,211 @@
-use std::io::{self, BufReader};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Write};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use lolcat_ultra::{Buffering, Config, InterruptGuard, LineEndings, ProcessError};
+
+mod cli;
+mod config_file;
+mod generate;
+
+use cli::{Animation, Command};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 const AUTHORS: &str = env!("CARGO_PKG_AUTHORS");
-const ABOUT: &str = "cat with rainbow colors";
 
-struct Args {
-    input: Option<std::path::PathBuf>,
-    frequency: f64,
-    spread: f64,
-    force: bool,
+/// How long processing gets to stop on its own after a signal, before the
+/// terminal is reset and the signal's default action taken regardless (e.g.
+/// while blocked reading)
+#[cfg(unix)]
+const INTERRUPT_GRACE: Duration = Duration::from_millis(200);
+
+// Exit codes, following sysexits(3)
+const EXIT_USAGE: i32 = 64;
+const EXIT_DATAERR: i32 = 65;
+const EXIT_NOINPUT: i32 = 66;
+const EXIT_IOERR: i32 = 74;
+
+/// The inputs read one after another as a single stream, like `cat`, so line
+/// numbers and squeezed empty lines carry over from one file to the next.
+/// Inputs that cannot be opened or read are reported and skipped.
+struct Inputs<'a> {
+    paths: std::vec::IntoIter<PathBuf>,
+    current: Option<(PathBuf, Box<dyn BufRead>)>,
+    program_name: &'a str,
+    /// Whether stdin's line endings are normalized, while those of files
+    /// are kept
+    normalize_stdin: bool,
+    /// Exit status for the inputs that could not be opened or read, or 0
+    status: i32,
+}
+
+impl<'a> Inputs<'a> {
+    fn new(paths: Vec<PathBuf>, program_name: &'a str, normalize_stdin: bool) -> Self {
+        Self {
+            paths: paths.into_iter(),
+            current: None,
+            program_name,
+            normalize_stdin,
+            status: 0,
+        }
+    }
+
+    fn open(&mut self, path: PathBuf) {
+        if path.as_os_str() == "-" {
+            let stdin = io::stdin().lock();
+            let reader: Box<dyn BufRead> = if self.normalize_stdin {
+                Box::new(NormalizedLines::new(stdin))
+            } else {
+                Box::new(stdin)
+            };
+            self.current = Some((path, reader));
+            return;
+        }
+        match File::open(&path) {
+            Ok(file) => self.current = Some((path, Box::new(BufReader::new(file)))),
+            Err(e) => {
+                eprintln!("{}: {}: {e}", self.program_name, path.display());
+                self.status = self.status.max(EXIT_NOINPUT);
+            }
+        }
+    }
+}
+
+impl Read for Inputs<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl BufRead for Inputs<'_> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        loop {
+            let Some((path, reader)) = &mut self.current else {
+                let Some(path) = self.paths.next() else {
+                    return Ok(&[]);
+                };
+                self.open(path);
+                continue;
+            };
+            match reader.fill_buf() {
+                Ok(available) if !available.is_empty() => break,
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    eprintln!("{}: {}: {e}", self.program_name, path.display());
+                    self.status = EXIT_IOERR;
+                }
+            }
+            self.current = None;
+        }
+        // Filled above, so this only returns the buffer
+        match &mut self.current {
+            Some((_, reader)) => reader.fill_buf(),
+            None => Ok(&[]),
+        }
+    }
+
+    fn consume(&mut self, amount: usize) {
+        if let Some((_, reader)) = &mut self.current {
+            reader.consume(amount);
+        }
+    }
+}
+
+/// An input with its line endings normalized like [`LineEndings::Normalize`]
+/// does: `\r\n` read as `\n`, and a last line without a newline given one
+struct NormalizedLines<R> {
+    inner: R,
+    buf: Vec<u8>,
+    pos: usize,
+    /// Whether what was read so far is empty or ends with a newline
+    line_ended: bool,
+    /// Whether the end of `inner` was reached
+    done: bool,
+}
+
+impl<R: BufRead> NormalizedLines<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buf: Vec::new(),
+            pos: 0,
+            line_ended: true,
+            done: false,
+        }
+    }
+
+    /// Refill the empty buffer from `inner`
+    fn refill(&mut self) -> io::Result<()> {
+        self.buf.clear();
+        self.pos = 0;
+        while self.buf.is_empty() {
+            let available = self.inner.fill_buf()?;
+            if available.is_empty() {
+                self.done = true;
+                if !self.line_ended {
+                    self.buf.push(b'\n');
+                }
+                return Ok(());
+            }
+            if available == b"\r" {
+                // Only known to start a `\r\n` once more is read
+                self.inner.consume(1);
+                if !self.inner.fill_buf()?.starts_with(b"\n") {
+                    self.buf.push(b'\r');
+                }
+                continue;
+            }
+            // A last `\r` is left for the next read, with what follows it
+            let end = available.len() - usize::from(available.ends_with(b"\r"));
+            let mut rest = &available[..end];
+            while let Some(cr) = rest.iter().position(|&b| b == b'\r') {
+                let crlf = rest.get(cr + 1) == Some(&b'\n');
+                self.buf.extend_from_slice(&rest[..cr + usize::from(!crlf)]);
+                rest = &rest[cr + 1..];
+            }
+            self.buf.extend_from_slice(rest);
+            self.inner.consume(end);
+        }
+        self.line_ended = self.buf.ends_with(b"\n");
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for NormalizedLines<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl<R: BufRead> BufRead for NormalizedLines<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.buf.len() && !self.done {
+            self.refill()?;
+        }
+        Ok(&self.buf[self.pos..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.pos = (self.pos + amount).min(self.buf.len());
+    }
+}
+
+/// Whether all of `paths` are regular files, which never keep a read waiting
+fn regular_files(paths: &[PathBuf]) -> bool {
+    paths.iter().all(|path| {
+        path.as_os_str() != "-" && fs::metadata(path).is_ok_and(|metadata| metadata.is_file())
+    })
 }
 
 /// Print text with rainbow colors using `process_input`
@@ -18,131 +215,293 @@ fn print_rainbow(text: &str) {
     let _ = lolcat_ultra::process_input(reader, &config);
 }
 
-fn print_help(program_name: &str) {
-    let help_text = format!(
-        "{ABOUT}\n\
-        \n\
-        Usage: {program_name} [OPTIONS] [FILE]\n\
-        \n\
-        Arguments:\n\
-        \x20 [FILE]  input file\n\
-        \n\
-        Options:\n\
-        \x20 -f, --frequency <FREQUENCY>  Color change frequency [default: 0.04]\n\
-        \x20 -s, --spread <SPREAD>        Rainbow spread [default: 4.0]\n\
-        \x20 -F, --force                  Force color even when stdout is not a tty\n\
-        \x20 -h, --help                   Print help\n\
-        \x20 -v, --version                Print version\n"
-    );
-    print_rainbow(&help_text);
-}
-
 fn print_version() {
     let version_text = format!("lolcat-ultra {VERSION}\nAuthors: {AUTHORS}\n");
     print_rainbow(&version_text);
 }
 
-fn parse_args() -> Result<Args, String> {
-    let mut args = std::env::args();
-    let program_name = args.next().unwrap_or_else(|| "lolcat-ultra".to_string());
+/// Map a processing error to the process exit code
+fn exit_code(err: &ProcessError) -> i32 {
+    match err {
+        // The reader of our output went away (e.g. `| head`); not a failure
+        ProcessError::BrokenPipe => 0,
+        ProcessError::InvalidConfig(_) => EXIT_USAGE,
+        ProcessError::LineLimit(_) => EXIT_DATAERR,
+        ProcessError::Read(_) | ProcessError::Write(_) => EXIT_IOERR,
+        // Only if the signal's default action did not end the process
+        ProcessError::Interrupted(signal) => 128 + signal,
+    }
+}
 
-    let mut input: Option<std::path::PathBuf> = None;
-    let mut frequency = 0.04;
-    let mut spread = 4.0;
-    let mut force = false;
+/// Reset the colors and show the cursor again, if stdout is a terminal
+fn reset_terminal() {
+    let mut stdout = io::stdout();
+    if stdout.is_terminal() {
+        let _ = stdout
+            .write_all(b"\x1b[0m\x1b[39m\x1b[49m\x1b[?25h")
+            .and_then(|()| stdout.flush());
+    }
+}
 
-    while let Some(arg) = args.next() {
-        match arg.as_str() {
-            "-h" | "--help" => {
-                print_help(&program_name);
-                std::process::exit(0);
-            }
-            "-v" | "--version" => {
-                print_version();
-                std::process::exit(0);
-            }
-            "-f" | "--frequency" => {
-                let value = args
-                    .next()
-                    .ok_or_else(|| format!("missing value for '{arg}'"))?;
-                frequency = value.parse().map_err(|_| {
-                    format!("invalid value '{value}' for '{arg}': expected a floating point number")
-                })?;
-            }
-            "-s" | "--spread" => {
-                let value = args
-                    .next()
-                    .ok_or_else(|| format!("missing value for '{arg}'"))?;
-                spread = value.parse().map_err(|_| {
-                    format!("invalid value '{value}' for '{arg}': expected a floating point number")
-                })?;
-            }
-            "-F" | "--force" => {
-                force = true;
-            }
-            arg if arg.starts_with('-') => {
-                return Err(format!("unknown option: {arg}"));
+/// End the process the way `signal` would have without a handler, so the
+/// caller sees it was killed by the signal
+fn exit_by_signal(signal: i32) -> ! {
+    #[cfg(unix)]
+    let _ = signal_hook::low_level::emulate_default_handler(signal);
+    // Like the default action, as reported by shells
+    std::process::exit(128 + signal);
+}
+
+/// Have signals stop processing, which resets the colors, instead of ending
+/// the process mid-sequence, for as long as the guard is kept
+fn handle_signals() -> Option<InterruptGuard> {
+    let guard = lolcat_ultra::handle_interrupts().ok();
+    #[cfg(unix)]
+    let _ = watch_signals();
+    guard
+}
+
+/// Take the default action of a signal that processing has not stopped for
+/// within [`INTERRUPT_GRACE`], such as while blocked reading
+#[cfg(unix)]
+fn watch_signals() -> io::Result<()> {
+    use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+    use signal_hook::iterator::Signals;
+
+    let mut signals = Signals::new([SIGINT, SIGTERM, SIGHUP])?;
+    thread::Builder::new()
+        .name("signals".to_string())
+        .spawn(move || {
+            if let Some(signal) = signals.forever().next() {
+                thread::sleep(INTERRUPT_GRACE);
+                reset_terminal();
+                exit_by_signal(signal);
             }
-            _ => {
-                if input.is_some() {
-                    return Err("unexpected argument: only one input file is allowed".to_string());
-                }
-                input = Some(std::path::PathBuf::from(arg));
+        })?;
+    Ok(())
+}
+
+/// Format an error followed by its chain of sources
+fn error_chain(err: &dyn std::error::Error) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        message.push_str(": ");
+        message.push_str(&err.to_string());
+        source = err.source();
+    }
+    message
+}
+
+/// Classify an error writing to stdout, like the library does
+fn write_error(err: io::Error) -> ProcessError {
+    if err.kind() == io::ErrorKind::BrokenPipe {
+        ProcessError::BrokenPipe
+    } else {
+        ProcessError::Write(err)
+    }
+}
+
+/// Show each line the way lolcat's `--animate` does: redrawn `duration`
+/// times, `speed` times a second, with the rainbow moving along each time
+fn animate(
+    reader: impl BufRead,
+    config: &Config,
+    animation: &Animation,
+) -> Result<usize, ProcessError> {
+    let mut stdout = io::stdout();
+    // The cursor would flicker back and forth with every frame
+    stdout.write_all(b"\x1b[?25l").map_err(write_error)?;
+    let result = animate_lines(reader, config, animation, &mut stdout);
+    stdout
+        .write_all(b"\x1b[?25h")
+        .and_then(|()| stdout.flush())
+        .map_err(write_error)?;
+    result
+}
+
+/// Number of columns `line` takes up, or more: escape sequences are counted
+/// too, which is harmless as the cursor stops at the first column
+fn line_width(line: &[u8], config: &Config) -> usize {
+    String::from_utf8_lossy(line)
+        .chars()
+        .fold(0, |column, c| match c {
+            '\t' => column + config.tab_stops.width_at(column),
+            _ => column + 1,
+        })
+}
+
+fn animate_lines(
+    reader: impl BufRead,
+    config: &Config,
+    animation: &Animation,
+    stdout: &mut io::Stdout,
+) -> Result<usize, ProcessError> {
+    // Each frame is the line alone; the newline follows the last frame
+    let mut config = config.clone();
+    config.line_endings = LineEndings::Preserve;
+    let first_line = config.first_line;
+    let offset = config.offset();
+    let frame_time = Duration::from_secs_f64(1.0 / animation.speed);
+
+    let mut lines = 0;
+    for line in reader.split(b'\n') {
+        let line = line.map_err(ProcessError::Read)?;
+        let line = line.strip_suffix(b"\r").unwrap_or(&line);
+        config.first_line = first_line + lines;
+        // Back to the start of the line for each frame by moving the cursor
+        // left as far as the line is wide, as lolcat does. Unlike a saved
+        // cursor position this still holds once the terminal scrolls.
+        let back = format!("\x1b[{}D", line_width(line, &config));
+        for frame in 1..=animation.duration {
+            if frame > 1 {
+                stdout.write_all(back.as_bytes()).map_err(write_error)?;
             }
+            config.set_offset(offset + frame as f64 * config.spread);
+            lolcat_ultra::process_input_to_writer(line, &mut *stdout, &config)?;
+            thread::sleep(frame_time);
         }
+        stdout.write_all(b"\n").map_err(write_error)?;
+        lines += 1;
     }
-
-    Ok(Args {
-        input,
-        frequency,
-        spread,
-        force,
-    })
+    Ok(lines)
 }
 
 fn main() {
     // Set up terminal cleanup to ensure proper reset on exit
     lolcat_ultra::setup_terminal_cleanup();
+    let _interrupts = handle_signals();
 
-    let program_name = std::env::args()
+    let mut command_line = std::env::args();
+    let program_name = command_line
         .next()
         .unwrap_or_else(|| "lolcat-ultra".to_string());
 
-    let args = match parse_args() {
+    let env_options = std::env::var("LOLCAT_OPTS").ok();
+    let args = match cli::parse_args(
+        &program_name,
+        env_options.as_deref(),
+        command_line.collect(),
+    ) {
         Ok(args) => args,
         Err(e) => {
             eprintln!("{program_name}: {e}");
-            std::process::exit(1);
+            std::process::exit(EXIT_USAGE);
         }
     };
+    match args.command {
+        Some(Command::Help) => {
+            print_rainbow(&cli::help(&program_name, args.compat));
+            std::process::exit(0);
+        }
+        Some(Command::Version) => {
+            print_version();
+            std::process::exit(0);
+        }
+        Some(Command::Man) => {
+            print!("{}", generate::man(VERSION));
+            std::process::exit(0);
+        }
+        Some(Command::Completions(shell)) => {
+            let shell = shell.expect("the parser requires a shell");
+            print!("{}", generate::completions(shell));
+            std::process::exit(0);
+        }
+        None => {}
+    }
 
-    // Validate and create config
-    let config = match lolcat_ultra::Config::try_new(args.frequency, args.spread, args.force) {
+    let mut config = match args.config() {
         Ok(config) => config,
         Err(e) => {
             eprintln!("{program_name}: {e}");
-            std::process::exit(1);
+            std::process::exit(EXIT_USAGE);
         }
     };
 
-    let result = if let Some(path) = args.input {
-        match std::fs::File::open(&path) {
-            Ok(file) => {
-                let reader = BufReader::new(file);
-                lolcat_ultra::process_input(reader, &config)
+    let inputs = if args.inputs.is_empty() {
+        vec![PathBuf::from("-")]
+    } else {
+        args.inputs
+    };
+    // Line endings in files are kept unless asked otherwise, while those
+    // read from stdin are normalized
+    config.line_endings = args.line_endings.unwrap_or(LineEndings::Preserve);
+    let normalize_stdin = args.line_endings.is_none();
+
+    let streams = if args.restart_per_file {
+        inputs.into_iter().map(|path| vec![path]).collect()
+    } else {
+        vec![inputs]
+    };
+
+    let mut lines_read = 0;
+    let mut status = 0;
+    for paths in streams {
+        config.max_lines = args.max_lines.map(|max| max.saturating_sub(lines_read));
+        // `Auto` flushes whenever the reader's buffer runs dry, which for a
+        // file is no sign of the input going idle
+        config.buffering = match args.buffering {
+            Buffering::Auto if !io::stdout().is_terminal() && regular_files(&paths) => {
+                Buffering::Block
             }
+            buffering => buffering,
+        };
+
+        let mut reader = Inputs::new(paths, &program_name, normalize_stdin);
+        let result = if args.animate && io::stdout().is_terminal() {
+            animate(&mut reader, &config, &args.animation)
+        } else {
+            lolcat_ultra::process_input(&mut reader, &config)
+        };
+        status = status.max(reader.status);
+
+        match result {
+            Ok(lines) => lines_read += lines,
+            // `--head` asks for truncation, so reaching the limit is success
+            Err(ProcessError::LineLimit(_)) if !args.report_truncation => break,
             Err(e) => {
-                eprintln!("{program_name}: {}: {e}", path.display());
-                std::process::exit(1);
+                // Report the limit over all inputs, not what was left of it
+                let e = match e {
+                    ProcessError::LineLimit(_) => {
+                        ProcessError::LineLimit(args.max_lines.unwrap_or_default())
+                    }
+                    e => e,
+                };
+                if let ProcessError::Interrupted(signal) = e {
+                    exit_by_signal(signal);
+                }
+                if !matches!(e, ProcessError::BrokenPipe) {
+                    eprintln!("{program_name}: {}", error_chain(&e));
+                }
+                std::process::exit(exit_code(&e));
             }
         }
-    } else {
-        let reader = BufReader::with_capacity(256 * 1024, io::stdin());
-        lolcat_ultra::process_input(reader, &config)
-    };
+    }
+    // An input that cannot be opened or read does not stop the others
+    std::process::exit(status);
+}
 
-    if let Err(e) = result {
-        eprintln!("{program_name}: {e}");
-        std::process::exit(1);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalized_lines_end_with_newlines() {
+        for (input, expected) in [
+            ("one\r\ntwo\r\n", "one\ntwo\n"),
+            ("cr\rlone\r\r\nlast", "cr\rlone\r\nlast\n"),
+            ("ends with cr\r", "ends with cr\r\n"),
+            ("\r\n\r\n", "\n\n"),
+            ("", ""),
+        ] {
+            for capacity in [1, 2, 3, 1024] {
+                let reader = BufReader::with_capacity(capacity, input.as_bytes());
+                let mut output = String::new();
+                NormalizedLines::new(reader)
+                    .read_to_string(&mut output)
+                    .unwrap();
+                assert_eq!(output, expected, "{input:?}, capacity {capacity}");
+            }
+        }
     }
 }
@@ -1,11 +1,15 @@
-use anyhow::{Context, Result};
 use arrayvec::ArrayVec;
-use std::io::{self, BufRead, BufWriter, Write};
+use std::io::{self, BufRead, BufWriter, IsTerminal, Write};
 
-use crate::ansi::process_ansi_escape_bytes;
-use crate::color::{ColorMode, detect_color_support};
-use crate::config::Config;
+use crate::ansi::{line_start_escape_len, process_ansi_escape_bytes, resume_ansi_escape_bytes};
+use crate::block::{Block, BlockLine, read_block};
+use crate::color::ColorMode;
+use crate::config::{
+    Buffering, CarriageReturns, Config, Decorations, Fit, Layer, LineEndings, LineNumbers, Tabs,
+};
+use crate::error::ProcessError;
 use crate::rainbow::RainbowLookup;
+use crate::terminal::interrupted;
 
 // Include the pre-computed 256-color ANSI cache from build time
 // (The rainbow tables are already included in rainbow.rs)
@@ -17,14 +21,14 @@ mod generated {
 
 /// Get cached ANSI sequence for a 256-color code
 #[inline]
-fn get_ansi_256(code: u8) -> &'static [u8] {
+pub(crate) fn get_ansi_256(code: u8) -> &'static [u8] {
     generated::ANSI_256_CACHE[code as usize]
 }
 
 /// Buffer capacity for line processing
 const BUF_CAP: usize = 8192;
 
-/// Helper to write ANSI TrueColor sequence to buffer
+/// Helper to write ANSI `TrueColor` sequence to buffer
 #[inline(always)]
 fn write_ansi_truecolor(buf: &mut ArrayVec<u8, BUF_CAP>, color_idx: usize, lookup: &RainbowLookup) {
     buf.try_extend_from_slice(lookup.get_truecolor_ansi(color_idx))
@@ -38,6 +42,40 @@ fn write_ansi_256color(buf: &mut ArrayVec<u8, BUF_CAP>, color_idx: usize, lookup
     buf.try_extend_from_slice(get_ansi_256(code)).unwrap();
 }
 
+/// Resets the background color, ending a line colored with [`Layer::Background`]
+pub(crate) const DEFAULT_BACKGROUND: &[u8] = b"\x1b[49m";
+
+/// Turn a foreground color sequence written to `buf` from `start` into the
+/// background one (`\x1b[38;` to `\x1b[48;`)
+#[inline(always)]
+pub(crate) fn set_background(buf: &mut [u8], start: usize) {
+    buf[start + 2] = b'4';
+}
+
+/// Helper to write ANSI `TrueColor` background sequence to buffer
+#[inline(always)]
+fn write_ansi_truecolor_bg(
+    buf: &mut ArrayVec<u8, BUF_CAP>,
+    color_idx: usize,
+    lookup: &RainbowLookup,
+) {
+    let start = buf.len();
+    write_ansi_truecolor(buf, color_idx, lookup);
+    set_background(buf, start);
+}
+
+/// Helper to write ANSI 256-color background sequence to buffer
+#[inline(always)]
+fn write_ansi_256color_bg(
+    buf: &mut ArrayVec<u8, BUF_CAP>,
+    color_idx: usize,
+    lookup: &RainbowLookup,
+) {
+    let start = buf.len();
+    write_ansi_256color(buf, color_idx, lookup);
+    set_background(buf, start);
+}
+
 /// Flush buffer if getting close to capacity
 #[inline]
 fn maybe_flush<W: Write>(writer: &mut W, buf: &mut ArrayVec<u8, BUF_CAP>) -> io::Result<()> {
@@ -49,82 +87,213 @@ fn maybe_flush<W: Write>(writer: &mut W, buf: &mut ArrayVec<u8, BUF_CAP>) -> io:
     Ok(())
 }
 
-/// Process a line with optimizations:
+/// Coloring state carried from one segment of a line to the next, so a line
+/// can be processed in pieces as it arrives without buffering all of it.
+struct LineState {
+    /// Phase at the start of the line
+    start_phase: u64,
+    phase: u64,
+    phase_inc: u64,
+    last_color_idx: Option<usize>,
+    /// Bytes consumed after ESC if an escape sequence was cut off mid-way
+    escape_len: Option<usize>,
+    /// Columns written so far, for tab stops
+    column: usize,
+    /// Where the line is in its block, if colored around the block's centre
+    block_line: Option<BlockLine>,
+}
+
+impl LineState {
+    #[inline]
+    fn new(phase: u64, phase_inc: u64) -> Self {
+        Self {
+            start_phase: phase,
+            phase,
+            phase_inc,
+            last_color_idx: None,
+            escape_len: None,
+            column: 0,
+            block_line: None,
+        }
+    }
+
+    /// Go back to the phase at the start of the line
+    #[inline]
+    fn rewind(&mut self) {
+        self.phase = self.start_phase;
+        self.last_color_idx = None;
+        self.column = 0;
+    }
+}
+
+/// Write a line segment without colors, expanding its tabs
+fn process_line_uncolored<W: Write>(
+    segment: &[u8],
+    state: &mut LineState,
+    config: &Config,
+    writer: &mut W,
+) -> io::Result<()> {
+    const SPACES: [u8; 16] = [b' '; 16];
+
+    let mut column = state.column;
+    let mut i = 0;
+    let mut escape_len = None;
+    if let Some(count) = state.escape_len {
+        (i, escape_len) = resume_ansi_escape_bytes(writer, segment, count)?;
+    }
+    // Start of the bytes copied as they are
+    let mut start = i;
+    while i < segment.len() {
+        match segment[i] {
+            0x1b => {
+                writer.write_all(&segment[start..i])?;
+                (i, escape_len) = process_ansi_escape_bytes(writer, segment, i)?;
+                start = i;
+            }
+            b'\t' => {
+                writer.write_all(&segment[start..i])?;
+                let mut width = config.tab_stops.width_at(column);
+                column += width;
+                while width > 0 {
+                    let spaces = width.min(SPACES.len());
+                    writer.write_all(&SPACES[..spaces])?;
+                    width -= spaces;
+                }
+                i += 1;
+                start = i;
+            }
+            b => {
+                if !(0x80..0xC0).contains(&b) {
+                    column += 1;
+                }
+                i += 1;
+            }
+        }
+    }
+    writer.write_all(&segment[start..])?;
+    state.escape_len = escape_len;
+    state.column = column;
+    Ok(())
+}
+
+/// Process a line segment with optimizations:
 /// - Pre-cached ANSI sequences (no itoa calls in hot loop)
 /// - Stack-allocated buffer (better cache locality)
 /// - Single final write (includes newline)
 /// - Track last color to avoid redundant ANSI sequences
 /// - Single color lookup per character
+///
+/// `line_end` is the line terminator to append after the last segment of a
+/// line, and empty for any other segment.
 fn process_line_streaming<W: Write>(
-    line: &[u8],
-    start_pos: f64,
+    segment: &[u8],
+    state: &mut LineState,
+    line_end: &[u8],
     config: &Config,
     color_mode: ColorMode,
     lookup: &RainbowLookup,
     writer: &mut W,
-) -> Result<()> {
-    debug_assert!(start_pos.is_finite(), "Start position must be finite");
-
+) -> io::Result<()> {
     // Dispatch to monomorphic implementation based on color mode
-    match color_mode {
-        ColorMode::NoColor => {
-            // Fast path: no color processing needed
-            writer
-                .write_all(line)
-                .context("Failed to write line without color")?;
-            writer
-                .write_all(b"\n")
-                .context("Failed to write newline without color")?;
-            Ok(())
+    match (color_mode, config.layer) {
+        (ColorMode::NoColor, _) => {
+            if config.tabs == Tabs::Preserve {
+                // Fast path: no color processing needed
+                writer.write_all(segment)?;
+            } else {
+                process_line_uncolored(segment, state, config, writer)?;
+            }
+            writer.write_all(line_end)
         }
-        ColorMode::TrueColor => process_line_with_color(
-            line,
-            start_pos,
+        (ColorMode::TrueColor, Layer::Foreground) => process_line_with_color(
+            segment,
+            state,
+            line_end,
             config,
             lookup,
             writer,
             write_ansi_truecolor,
         ),
-        ColorMode::Color256 => {
-            process_line_with_color(line, start_pos, config, lookup, writer, write_ansi_256color)
-        }
+        (ColorMode::Color256, Layer::Foreground) => process_line_with_color(
+            segment,
+            state,
+            line_end,
+            config,
+            lookup,
+            writer,
+            write_ansi_256color,
+        ),
+        (ColorMode::TrueColor, Layer::Background) => process_line_with_color(
+            segment,
+            state,
+            line_end,
+            config,
+            lookup,
+            writer,
+            write_ansi_truecolor_bg,
+        ),
+        (ColorMode::Color256, Layer::Background) => process_line_with_color(
+            segment,
+            state,
+            line_end,
+            config,
+            lookup,
+            writer,
+            write_ansi_256color_bg,
+        ),
     }
 }
 
 /// Monomorphic color processing implementation
 /// This function is generic over the ANSI writer to enable complete inlining.
 /// Uses byte-level iteration to avoid UTF-8 decoding overhead — only \x1b and \t
-/// need detection (both single-byte ASCII). Multi-byte codepoints are copied as
+/// need detection (both single-byte ASCII). Tabs advance to the next tab stop,
+/// counting columns in codepoints. Multi-byte codepoints are copied as
 /// raw bytes; the phase counter advances only on codepoint-start bytes.
 #[inline]
+#[allow(clippy::too_many_lines)]
 fn process_line_with_color<W: Write, F>(
-    line: &[u8],
-    start_pos: f64,
+    segment: &[u8],
+    state: &mut LineState,
+    line_end: &[u8],
     config: &Config,
     lookup: &RainbowLookup,
     writer: &mut W,
     write_ansi: F,
-) -> Result<()>
+) -> io::Result<()>
 where
     F: Fn(&mut ArrayVec<u8, BUF_CAP>, usize, &RainbowLookup),
 {
+    if let Some(block_line) = state.block_line {
+        return process_centred_line_with_color(
+            segment, state, block_line, line_end, config, lookup, writer, write_ansi,
+        );
+    }
+
     // Stack-allocated buffer - 8KB for better cache locality
     let mut buf = ArrayVec::<u8, BUF_CAP>::new();
 
-    // Fixed-point phase accumulator - eliminates all float ops in hot path
-    let pos_increment = 1.0 / config.spread;
-    let (mut phase, phase_inc) = lookup.fixedpoint_phase(start_pos, pos_increment);
+    let mut phase = state.phase;
+    let phase_inc = state.phase_inc;
 
     // Track last color index to avoid redundant ANSI sequences
-    let mut last_color_idx: Option<usize> = None;
+    let mut last_color_idx = state.last_color_idx;
 
-    let bytes = line;
+    let mut column = state.column;
+
+    let bytes = segment;
     let len = bytes.len();
     let mut i = 0;
 
+    // Finish an escape sequence cut off by the end of the previous segment
+    let mut escape_len = None;
+    if let Some(count) = state.escape_len {
+        (i, escape_len) = resume_ansi_escape_bytes(writer, bytes, count)?;
+    }
+
     // Optimization: if phase_inc is small, we can process chunks of characters
     // that share the same color index without recalculating it.
-    if phase_inc > 0 && phase_inc < (1 << 28) {
+    if lookup.changes_slowly(phase_inc) {
         while i < len {
             let b = bytes[i];
 
@@ -134,14 +303,22 @@ where
                     writer.write_all(&buf)?;
                     buf.clear();
                 }
-                i = process_ansi_escape_bytes(writer, bytes, i)?;
+                (i, escape_len) = process_ansi_escape_bytes(writer, bytes, i)?;
                 last_color_idx = None;
                 continue;
             }
 
             if b == b'\t' {
                 i += 1;
-                for _ in 0..8 {
+                let width = config.tab_stops.width_at(column);
+                column += width;
+                if config.tabs == Tabs::Preserve {
+                    buf.push(b'\t');
+                    phase = phase.wrapping_add(phase_inc.wrapping_mul(width as u64));
+                    maybe_flush(writer, &mut buf)?;
+                    continue;
+                }
+                for _ in 0..width {
                     let color_idx = lookup.color_index_from_phase(phase);
                     if last_color_idx != Some(color_idx) {
                         write_ansi(&mut buf, color_idx, lookup);
@@ -154,6 +331,14 @@ where
                 continue;
             }
 
+            // Continuation bytes left over from a codepoint split by a flush or
+            // a segment boundary: a color sequence must not go in front of them
+            if (0x80..0xC0).contains(&b) {
+                buf.push(b);
+                i += 1;
+                continue;
+            }
+
             // Normal character batching
             let color_idx = lookup.color_index_from_phase(phase);
             if last_color_idx != Some(color_idx) {
@@ -175,7 +360,7 @@ where
                 }
                 // Check codepoint-start: if we've already hit max_run,
                 // stop before starting a new codepoint
-                if b2 < 0x80 || b2 >= 0xC0 {
+                if !(0x80..0xC0).contains(&b2) {
                     if processed >= max_run {
                         break;
                     }
@@ -186,6 +371,7 @@ where
             }
 
             if processed > 0 {
+                column += processed;
                 phase = phase.wrapping_add(phase_inc.wrapping_mul(processed as u64));
                 maybe_flush(writer, &mut buf)?;
             }
@@ -195,13 +381,15 @@ where
         // Each char needs at most 20 bytes (19-byte ANSI + 1-byte char).
         // If the entire line fits in the buffer AND contains no special bytes,
         // skip per-char ESC/tab/capacity/UTF-8 checks entirely.
-        let fits_in_buf = len <= (BUF_CAP - 2) / 20;
-        let no_special = fits_in_buf && !bytes.iter().any(|&b| b == 0x1b || b == b'\t');
-        let all_ascii = no_special && bytes.iter().all(|&b| b < 0x80);
+        let rest = &bytes[i..];
+        let fits_in_buf = rest.len() <= (BUF_CAP - 2) / 20;
+        let no_special = fits_in_buf && !rest.iter().any(|&b| b == 0x1b || b == b'\t');
+        let all_ascii = no_special && rest.iter().all(|&b| b < 0x80);
 
         if all_ascii {
             // Tightest inner loop: pure ASCII, no special bytes, buffer won't fill.
             // No ESC/tab/capacity/UTF-8 checks needed.
+            column += rest.len();
             while i < len {
                 let color_idx = lookup.color_index_from_phase(phase);
                 if last_color_idx != Some(color_idx) {
@@ -216,13 +404,14 @@ where
             // ASCII + UTF-8 but no ESC/tab; no capacity check needed.
             while i < len {
                 let b = bytes[i];
-                if b < 0x80 || b >= 0xC0 {
+                if !(0x80..0xC0).contains(&b) {
                     let color_idx = lookup.color_index_from_phase(phase);
                     if last_color_idx != Some(color_idx) {
                         write_ansi(&mut buf, color_idx, lookup);
                         last_color_idx = Some(color_idx);
                     }
                     phase = phase.wrapping_add(phase_inc);
+                    column += 1;
                 }
                 buf.push(b);
                 i += 1;
@@ -237,14 +426,25 @@ where
                         writer.write_all(&buf)?;
                         buf.clear();
                     }
-                    i = process_ansi_escape_bytes(writer, bytes, i)?;
+                    (i, escape_len) = process_ansi_escape_bytes(writer, bytes, i)?;
                     last_color_idx = None;
                     continue;
                 }
 
                 if b == b'\t' {
                     i += 1;
-                    for _ in 0..8 {
+                    let width = config.tab_stops.width_at(column);
+                    column += width;
+                    if config.tabs == Tabs::Preserve {
+                        if buf.remaining_capacity() < 32 {
+                            writer.write_all(&buf)?;
+                            buf.clear();
+                        }
+                        buf.push(b'\t');
+                        phase = phase.wrapping_add(phase_inc.wrapping_mul(width as u64));
+                        continue;
+                    }
+                    for _ in 0..width {
                         // Flush before ANSI write; space (1 byte) always fits after
                         if buf.remaining_capacity() < 32 {
                             writer.write_all(&buf)?;
@@ -264,7 +464,7 @@ where
                 // Codepoint-start bytes: flush if needed, emit color, advance phase.
                 // Continuation bytes (0x80–0xBF) just get pushed; headroom is guaranteed
                 // by the flush check on each start byte (max 19-byte ANSI + 4-byte codepoint < 32).
-                if b < 0x80 || b >= 0xC0 {
+                if !(0x80..0xC0).contains(&b) {
                     if buf.remaining_capacity() < 32 {
                         writer.write_all(&buf)?;
                         buf.clear();
@@ -275,6 +475,7 @@ where
                         last_color_idx = Some(color_idx);
                     }
                     phase = phase.wrapping_add(phase_inc);
+                    column += 1;
                 }
 
                 buf.push(b);
@@ -283,148 +484,747 @@ where
         }
     }
 
-    // Append newline and write in one syscall
-    buf.push(b'\n');
-    writer
-        .write_all(&buf)
-        .context("Failed to write final buffered line")?;
+    state.phase = phase;
+    state.last_color_idx = last_color_idx;
+    state.escape_len = escape_len;
+    state.column = column;
 
-    Ok(())
+    end_segment(&mut buf, state, line_end, config, writer)
+}
+
+/// Color processing for a line of a block colored around its centre, where
+/// the phase is not a fixed step per column and is worked out for each
+/// codepoint instead
+#[allow(clippy::too_many_arguments)]
+fn process_centred_line_with_color<W: Write, F>(
+    segment: &[u8],
+    state: &mut LineState,
+    block_line: BlockLine,
+    line_end: &[u8],
+    config: &Config,
+    lookup: &RainbowLookup,
+    writer: &mut W,
+    write_ansi: F,
+) -> io::Result<()>
+where
+    F: Fn(&mut ArrayVec<u8, BUF_CAP>, usize, &RainbowLookup),
+{
+    let mut buf = ArrayVec::<u8, BUF_CAP>::new();
+    let mut last_color_idx = state.last_color_idx;
+    let mut column = state.column;
+
+    // Color sequence for `column`, unless it already has the last color
+    let write_color =
+        |buf: &mut ArrayVec<u8, BUF_CAP>, last_color_idx: &mut Option<usize>, column: usize| {
+            let phase = lookup
+                .fixedpoint_phase(block_line.position(column, config), 0.0)
+                .0;
+            let color_idx = lookup.color_index_from_phase(phase);
+            if *last_color_idx != Some(color_idx) {
+                write_ansi(buf, color_idx, lookup);
+                *last_color_idx = Some(color_idx);
+            }
+        };
+
+    let mut i = 0;
+    let mut escape_len = None;
+    if let Some(count) = state.escape_len {
+        (i, escape_len) = resume_ansi_escape_bytes(writer, segment, count)?;
+    }
+
+    while i < segment.len() {
+        let b = segment[i];
+
+        if b == 0x1b {
+            if !buf.is_empty() {
+                writer.write_all(&buf)?;
+                buf.clear();
+            }
+            (i, escape_len) = process_ansi_escape_bytes(writer, segment, i)?;
+            last_color_idx = None;
+            continue;
+        }
+
+        // Room for a color sequence and a codepoint (or a tab)
+        if buf.remaining_capacity() < 32 {
+            writer.write_all(&buf)?;
+            buf.clear();
+        }
+        i += 1;
+
+        if b == b'\t' {
+            let width = config.tab_stops.width_at(column);
+            if config.tabs == Tabs::Preserve {
+                buf.push(b'\t');
+                column += width;
+                continue;
+            }
+            for _ in 0..width {
+                if buf.remaining_capacity() < 32 {
+                    writer.write_all(&buf)?;
+                    buf.clear();
+                }
+                write_color(&mut buf, &mut last_color_idx, column);
+                buf.push(b' ');
+                column += 1;
+            }
+            continue;
+        }
+
+        if !(0x80..0xC0).contains(&b) {
+            write_color(&mut buf, &mut last_color_idx, column);
+            column += 1;
+        }
+        buf.push(b);
+    }
+
+    state.last_color_idx = last_color_idx;
+    state.escape_len = escape_len;
+    state.column = column;
+
+    end_segment(&mut buf, state, line_end, config, writer)
+}
+
+/// Write what is left of a segment, followed by `line_end`
+#[inline]
+fn end_segment<W: Write>(
+    buf: &mut ArrayVec<u8, BUF_CAP>,
+    state: &mut LineState,
+    line_end: &[u8],
+    config: &Config,
+    writer: &mut W,
+) -> io::Result<()> {
+    // Keep a colored background from running on past the line
+    if config.layer == Layer::Background && !line_end.is_empty() {
+        if buf.remaining_capacity() < DEFAULT_BACKGROUND.len() {
+            writer.write_all(buf)?;
+            buf.clear();
+        }
+        buf.try_extend_from_slice(DEFAULT_BACKGROUND).unwrap();
+        state.last_color_idx = None;
+    }
+
+    // Append the line terminator and write in one syscall
+    if buf.remaining_capacity() < line_end.len() {
+        writer.write_all(buf)?;
+        buf.clear();
+    }
+    // Byte by byte, as a `memcpy` call costs more than the byte or two
+    for &b in line_end {
+        buf.push(b);
+    }
+    writer.write_all(buf)
 }
 
 /// Optimized batch processing for better performance with large inputs
 struct BatchProcessor<W: Write> {
     writer: BufWriter<W>,
     lookup: RainbowLookup,
+    lines_read: usize,
+    /// State of the line currently being processed, if it has been started
+    /// by a segment that did not reach its end
+    line: Option<LineState>,
+    /// A `\r` ending the previous segment, held back in case it starts a
+    /// `\r\n` line ending
+    pending_cr: bool,
+    /// Number of the last line given a line number
+    line_number: usize,
+    /// Whether anything has been written for the current line, so its line
+    /// number (if any) is already written
+    line_started: bool,
+    /// Whether the last line written was empty, for squeezing empty lines
+    after_blank: bool,
+    /// The block being colored, for directions around the centre of the text
+    block: Option<Block>,
+    /// [`Config::line_step`], worked out once rather than for every line
+    line_step: f64,
+    /// Fixed-point phase increment per column for the current lookup
+    phase_inc: u64,
+}
+
+/// Largest piece of line content made visible at once; a byte is shown as at
+/// most four (`M-^X`)
+const SHOWN_CHUNK: usize = 1024;
+
+/// Append `b` the way `cat -v` and `cat -T` show it
+#[inline]
+fn push_shown(shown: &mut ArrayVec<u8, { 4 * SHOWN_CHUNK }>, b: u8, decorations: Decorations) {
+    let visible = if b == b'\t' {
+        decorations.show_tabs
+    } else {
+        decorations.show_nonprinting
+    };
+    if !visible {
+        shown.push(b);
+        return;
+    }
+    let mut b = b;
+    if b >= 0x80 {
+        shown.extend(*b"M-");
+        b -= 0x80;
+    }
+    match b {
+        0..0x20 => shown.extend([b'^', b + 0x40]),
+        0x7F => shown.extend(*b"^?"),
+        _ => shown.push(b),
+    }
 }
 
 impl<W: Write> BatchProcessor<W> {
     fn new(writer: W, config: &Config) -> Self {
         // Use a larger buffer size for better performance with large files
         const BUFFER_SIZE: usize = 256 * 1024; // 256KB buffer
+        let lookup = RainbowLookup::new(config.frequency);
         Self {
             writer: BufWriter::with_capacity(BUFFER_SIZE, writer),
-            lookup: RainbowLookup::new(config.frequency),
+            phase_inc: lookup.fixedpoint_phase(0.0, config.column_step()).1,
+            lookup,
+            lines_read: 0,
+            line: None,
+            pending_cr: false,
+            line_number: 0,
+            line_started: false,
+            after_blank: false,
+            block: None,
+            line_step: config.line_step(),
         }
     }
 
-    fn process_line(
+    /// Measure a block about to be processed, to place the rainbow around
+    /// its centre or fit the rainbow to it
+    fn start_block(&mut self, text: &[u8], config: &Config) {
+        let block = Block::measure(text, self.lines_read, config);
+        if config.fit != Fit::Off {
+            let frequency = block.fitted_frequency(config).unwrap_or(config.frequency);
+            self.lookup = RainbowLookup::new(frequency);
+            self.phase_inc = self.lookup.fixedpoint_phase(0.0, config.column_step()).1;
+        }
+        self.block = config.direction.is_centred().then_some(block);
+    }
+
+    /// Whether a line has been started but not yet ended
+    fn in_line(&self) -> bool {
+        self.line.is_some()
+    }
+
+    /// Take the state of the current line, starting a new one if needed
+    #[inline]
+    fn take_line_state(&mut self, config: &Config) -> LineState {
+        self.line
+            .take()
+            .unwrap_or_else(|| self.new_line_state(config))
+    }
+
+    /// State for the start of the next line
+    #[inline]
+    fn new_line_state(&self, config: &Config) -> LineState {
+        // Like `Config::line_start_pos`, without working out the step again
+        let start_pos =
+            ((config.first_line + self.lines_read) as f64) * self.line_step + config.offset();
+        debug_assert!(start_pos.is_finite(), "Start position must be finite");
+
+        // Fixed-point phase accumulator - eliminates all float ops in hot path
+        let phase = self.lookup.fixedpoint_phase(start_pos, 0.0).0;
+        let mut state = LineState::new(phase, self.phase_inc);
+        state.block_line = self.block.map(|block| block.line(self.lines_read));
+        state
+    }
+
+    #[inline]
+    fn write_segment(
         &mut self,
-        line: &[u8],
-        start_pos: f64,
+        segment: &[u8],
+        state: &mut LineState,
+        line_end: &[u8],
         config: &Config,
         color_mode: ColorMode,
-    ) -> Result<()> {
+    ) -> Result<(), ProcessError> {
         process_line_streaming(
-            line,
-            start_pos,
+            segment,
+            state,
+            line_end,
             config,
             color_mode,
             &self.lookup,
             &mut self.writer,
-        )?;
+        )
+        .map_err(ProcessError::write)
+    }
+
+    /// Write line content, followed by `line_end`. With
+    /// [`CarriageReturns::Rewind`] each `\r`, or escape sequence moving the
+    /// cursor to the first column, is written uncolored, restarts the line's
+    /// phase and flushes the output.
+    #[inline]
+    fn write_text(
+        &mut self,
+        text: &[u8],
+        state: &mut LineState,
+        line_end: &[u8],
+        config: &Config,
+        color_mode: ColorMode,
+    ) -> Result<(), ProcessError> {
+        let mut rest = text;
+        if config.carriage_returns == CarriageReturns::Rewind {
+            while let Some((at, len)) = next_rewind(rest) {
+                let (text, rewind) = rest[..at + len].split_at(at);
+                self.write_segment(text, state, rewind, config, color_mode)?;
+                self.writer.flush().map_err(ProcessError::write)?;
+                state.rewind();
+                rest = &rest[at + len..];
+            }
+        }
+        self.write_segment(rest, state, line_end, config, color_mode)
+    }
+
+    /// Write line content, showing it as the decorations ask
+    #[inline]
+    fn write_content(
+        &mut self,
+        text: &[u8],
+        state: &mut LineState,
+        config: &Config,
+        color_mode: ColorMode,
+    ) -> Result<(), ProcessError> {
+        let decorations = config.decorations;
+        if !decorations.any() {
+            return self.write_text(text, state, b"", config, color_mode);
+        }
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.start_output_line(state, false, config, color_mode)?;
+        if !decorations.show_tabs && !decorations.show_nonprinting {
+            return self.write_text(text, state, b"", config, color_mode);
+        }
+        let mut shown = ArrayVec::<u8, { 4 * SHOWN_CHUNK }>::new();
+        for chunk in text.chunks(SHOWN_CHUNK) {
+            shown.clear();
+            for &b in chunk {
+                push_shown(&mut shown, b, decorations);
+            }
+            self.write_text(&shown, state, b"", config, color_mode)?;
+        }
+        Ok(())
+    }
+
+    /// Write the line number, if the line gets one, before the first thing
+    /// written for a line
+    fn start_output_line(
+        &mut self,
+        state: &mut LineState,
+        blank: bool,
+        config: &Config,
+        color_mode: ColorMode,
+    ) -> Result<(), ProcessError> {
+        if std::mem::replace(&mut self.line_started, true) {
+            return Ok(());
+        }
+        let numbered = match config.decorations.line_numbers {
+            LineNumbers::Off => false,
+            LineNumbers::All => true,
+            LineNumbers::NonBlank => !blank,
+        };
+        if !numbered {
+            return Ok(());
+        }
+        self.line_number += 1;
+        let mut digits = itoa::Buffer::new();
+        let digits = digits.format(self.line_number).as_bytes();
+        // Formatted like `cat -n`: right-aligned in six columns, then a tab
+        let mut prefix = ArrayVec::<u8, 32>::new();
+        prefix.extend(std::iter::repeat_n(
+            b' ',
+            6usize.saturating_sub(digits.len()),
+        ));
+        prefix.extend(digits.iter().copied());
+        prefix.push(b'\t');
+        self.write_text(&prefix, state, b"", config, color_mode)
+    }
+
+    /// Write the rest of a line and its line ending, unless it is an empty
+    /// line squeezed out after another one
+    #[inline]
+    fn write_line_end(
+        &mut self,
+        rest: &[u8],
+        mut state: LineState,
+        line_end: &[u8],
+        config: &Config,
+        color_mode: ColorMode,
+    ) -> Result<(), ProcessError> {
+        let decorations = config.decorations;
+        if decorations.any() {
+            let blank = !self.line_started && rest.is_empty();
+            if blank && decorations.squeeze_blank && self.after_blank {
+                // Still counted, so the input lines keep their place in the
+                // rainbow and in a block
+                self.lines_read += 1;
+                return Ok(());
+            }
+            self.after_blank = blank;
+            self.write_content(rest, &mut state, config, color_mode)?;
+            self.start_output_line(&mut state, blank, config, color_mode)?;
+            let end: &[u8] = if decorations.show_ends && !line_end.is_empty() {
+                b"$"
+            } else {
+                b""
+            };
+            self.write_text(end, &mut state, line_end, config, color_mode)?;
+            self.line_started = false;
+        } else {
+            self.write_text(rest, &mut state, line_end, config, color_mode)?;
+        }
+        self.lines_read += 1;
+        Ok(())
+    }
+
+    /// Process part of a line whose end has not been read yet
+    fn process_segment(
+        &mut self,
+        segment: &[u8],
+        config: &Config,
+        color_mode: ColorMode,
+    ) -> Result<(), ProcessError> {
+        let mut state = self.take_line_state(config);
+        if std::mem::take(&mut self.pending_cr) {
+            self.write_content(b"\r", &mut state, config, color_mode)?;
+        }
+        let segment = match segment.split_last() {
+            Some((b'\r', rest)) if !config.decorations.show_nonprinting => {
+                self.pending_cr = true;
+                rest
+            }
+            _ => segment,
+        };
+        self.write_content(segment, &mut state, config, color_mode)?;
+        if self.pending_cr && config.carriage_returns == CarriageReturns::Rewind {
+            // Show what was drawn so far; the held-back `\r` only moves the
+            // cursor, which can wait for the next redraw
+            self.writer.flush().map_err(ProcessError::write)?;
+        }
+        self.line = Some(state);
         Ok(())
     }
 
-    fn finish(mut self) -> Result<()> {
-        // Comprehensive terminal reset sequence
-        write!(self.writer, "\x1b[0m\x1b[39m\x1b[49m").context("Failed to write terminal reset")?;
-        self.writer.flush().context("Failed to flush final batch")
+    /// Process the rest of a line up to (not including) its `\n`
+    #[inline]
+    fn end_line(
+        &mut self,
+        rest: &[u8],
+        config: &Config,
+        color_mode: ColorMode,
+    ) -> Result<(), ProcessError> {
+        // Most lines arrive whole with nothing to show or rewind; they go
+        // straight to the coloring, which is most of the time spent per line
+        if self.line.is_none()
+            && !self.pending_cr
+            && config.carriage_returns == CarriageReturns::Character
+            && !config.decorations.any()
+        {
+            let (rest, line_end): (&[u8], &[u8]) = match rest.strip_suffix(b"\r") {
+                Some(rest) if config.line_endings == LineEndings::Preserve => (rest, b"\r\n"),
+                Some(rest) => (rest, b"\n"),
+                None => (rest, b"\n"),
+            };
+            let mut state = self.new_line_state(config);
+            self.lines_read += 1;
+            return self.write_segment(rest, &mut state, line_end, config, color_mode);
+        }
+
+        let mut state = self.take_line_state(config);
+        let pending_cr = std::mem::take(&mut self.pending_cr);
+        let (rest, crlf) = if config.decorations.show_nonprinting {
+            // Shown as `^M` rather than taken as part of the line ending
+            (rest, false)
+        } else if let Some(rest) = rest.strip_suffix(b"\r") {
+            // A held-back `\r` was not part of the line ending after all
+            if pending_cr {
+                self.write_content(b"\r", &mut state, config, color_mode)?;
+            }
+            (rest, true)
+        } else if pending_cr && !rest.is_empty() {
+            self.write_content(b"\r", &mut state, config, color_mode)?;
+            (rest, false)
+        } else {
+            // A held-back `\r` directly before the `\n` is the line ending
+            (rest, pending_cr)
+        };
+        let line_end: &[u8] = match config.line_endings {
+            LineEndings::Preserve if crlf => b"\r\n",
+            _ => b"\n",
+        };
+        self.write_line_end(rest, state, line_end, config, color_mode)
+    }
+
+    /// End a final line that has no trailing newline
+    fn end_input(&mut self, config: &Config, color_mode: ColorMode) -> Result<(), ProcessError> {
+        if let Some(state) = self.line.take() {
+            let rest: &[u8] = if std::mem::take(&mut self.pending_cr) {
+                b"\r"
+            } else {
+                b""
+            };
+            let line_end: &[u8] = match config.line_endings {
+                LineEndings::Normalize => b"\n",
+                LineEndings::Preserve => b"",
+            };
+            self.write_line_end(rest, state, line_end, config, color_mode)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), ProcessError> {
+        self.writer.flush().map_err(ProcessError::write)
+    }
+
+    fn finish(mut self, color_mode: ColorMode) -> Result<(), ProcessError> {
+        if color_mode != ColorMode::NoColor {
+            // Comprehensive terminal reset sequence
+            self.writer
+                .write_all(b"\x1b[0m\x1b[39m\x1b[49m")
+                .map_err(ProcessError::write)?;
+        }
+        self.flush()
+    }
+}
+
+/// Where the next return to the start of the line is in `text`, and its
+/// length: a `\r`, or an escape sequence moving the cursor to the first
+/// column
+fn next_rewind(text: &[u8]) -> Option<(usize, usize)> {
+    let mut from = 0;
+    while let Some(i) = text[from..].iter().position(|&b| b == b'\r' || b == 0x1b) {
+        let at = from + i;
+        if text[at] == b'\r' {
+            return Some((at, 1));
+        }
+        if let Some(len) = line_start_escape_len(&text[at..]) {
+            return Some((at, len));
+        }
+        from = at + 1;
+    }
+    None
+}
+
+/// Copy input to output unchanged, stopping after `max_lines` lines.
+/// Returns the number of lines copied.
+// The newline count auto-vectorizes well enough not to need `bytecount`
+#[allow(clippy::naive_bytecount)]
+fn copy_input<R: BufRead, W: Write>(
+    mut reader: R,
+    mut writer: W,
+    max_lines: Option<usize>,
+    buffering: Buffering,
+    carriage_returns: CarriageReturns,
+) -> Result<usize, ProcessError> {
+    let max_lines = max_lines.unwrap_or(usize::MAX);
+    let mut lines = 0;
+    // Whether the last byte copied left a line without its newline
+    let mut in_line = false;
+    loop {
+        if let Some(signal) = interrupted() {
+            return Err(ProcessError::Interrupted(signal));
+        }
+        let available = reader.fill_buf().map_err(ProcessError::Read)?;
+        if available.is_empty() {
+            return Ok(lines + usize::from(in_line));
+        }
+        if lines >= max_lines {
+            return Err(ProcessError::LineLimit(max_lines));
+        }
+
+        let end = if max_lines == usize::MAX {
+            lines += available.iter().filter(|&&b| b == b'\n').count();
+            available.len()
+        } else {
+            let mut end = available.len();
+            for (i, &b) in available.iter().enumerate() {
+                if b == b'\n' {
+                    lines += 1;
+                    if lines == max_lines {
+                        end = i + 1;
+                        break;
+                    }
+                }
+            }
+            end
+        };
+        in_line = available[end - 1] != b'\n';
+
+        // Progress bars redrawn in place should show even when buffering
+        let rewinds =
+            carriage_returns == CarriageReturns::Rewind && next_rewind(&available[..end]).is_some();
+        writer
+            .write_all(&available[..end])
+            .map_err(ProcessError::write)?;
+        reader.consume(end);
+        // Every read is written straight through; only a buffering writer
+        // (such as stdout holding a partial line) needs telling
+        if buffering != Buffering::Block || rewinds {
+            writer.flush().map_err(ProcessError::write)?;
+        }
     }
 }
 
 /// Process input with a specific color mode (for testing/benchmarking)
 ///
+/// Returns the number of input lines read, counting a last line without a
+/// newline and empty lines dropped by [`Decorations::squeeze_blank`]. With
+/// [`Config::first_line`] set to the total of earlier calls, the next input
+/// continues the rainbow where the previous one stopped.
+///
 /// # Errors
 ///
 /// Returns an error if:
-/// - Reading from the input reader fails
-/// - Writing to the output writer fails
-/// - Maximum line limit is exceeded
+/// - The configuration is invalid (`ProcessError::InvalidConfig`)
+/// - Reading from the input reader fails (`ProcessError::Read`)
+/// - Writing to the output writer fails (`ProcessError::Write`, or
+///   `ProcessError::BrokenPipe` if the reader of the output went away)
+/// - Maximum line limit is exceeded (`ProcessError::LineLimit`)
+/// - A signal handled after [`handle_interrupts`](crate::handle_interrupts)
+///   stopped processing (`ProcessError::Interrupted`)
 pub fn process_input_with_color_mode<R: BufRead, W: Write>(
-    mut reader: R,
+    reader: R,
     writer: W,
     config: &Config,
     color_mode: ColorMode,
-) -> Result<()> {
-    // Maximum number of lines to process to ensure statically provable upper bound
-    // This prevents infinite loops when reading from stdin or very large files
-    const MAX_LINES: usize = 1_000_000_000;
-
-    // Fast path: when no color, just copy input to output like cat
-    if color_mode == ColorMode::NoColor {
-        let mut writer = writer;
-        loop {
-            let n = reader.fill_buf().context("Failed to read input")?;
-            if n.is_empty() {
-                break;
-            }
-            writer.write_all(n).context("Failed to write output")?;
-            let n = n.len();
-            reader.consume(n);
-        }
-        return Ok(());
+) -> Result<usize, ProcessError> {
+    config.validate()?;
+
+    // Fast path: when no color, just copy input to output like cat, unless
+    // the text itself is changed
+    if color_mode == ColorMode::NoColor
+        && !config.decorations.any()
+        && config.line_endings == LineEndings::Preserve
+        && config.tabs == Tabs::Preserve
+    {
+        return copy_input(
+            reader,
+            writer,
+            config.max_lines,
+            config.buffering,
+            config.carriage_returns,
+        );
     }
 
     // Color processing path
     let mut processor = BatchProcessor::new(writer, config);
-    // line_buf is only used for the rare case where a line spans two buffer fills
-    let mut line_buf: Vec<u8> = Vec::with_capacity(1024);
-    let mut lines_read = 0;
+
+    let buffered = config.direction.is_centred() || config.fit != Fit::Off;
+    let processed = if buffered && color_mode != ColorMode::NoColor {
+        process_blocks(reader, &mut processor, config, color_mode)
+    } else {
+        process_lines(reader, &mut processor, config, color_mode)
+    };
+    if let Err(err) = processed {
+        // Reset the colors when stopping partway, unless writing is what failed
+        if !matches!(err, ProcessError::Write(_) | ProcessError::BrokenPipe) {
+            processor.finish(color_mode)?;
+        }
+        return Err(err);
+    }
+
+    if processor.in_line() {
+        processor.end_input(config, color_mode)?;
+    }
+    let lines = processor.lines_read;
+    processor.finish(color_mode)?;
+    Ok(lines)
+}
+
+/// Color the lines of `reader` until it is drained, leaving a last line
+/// without a newline unfinished
+fn process_lines<R: BufRead, W: Write>(
+    mut reader: R,
+    processor: &mut BatchProcessor<W>,
+    config: &Config,
+    color_mode: ColorMode,
+) -> Result<(), ProcessError> {
+    let max_lines = config.max_lines.unwrap_or(usize::MAX);
 
     loop {
-        if lines_read >= MAX_LINES {
-            break;
+        if let Some(signal) = interrupted() {
+            return Err(ProcessError::Interrupted(signal));
+        }
+        let available = reader.fill_buf().map_err(ProcessError::Read)?;
+        if available.is_empty() {
+            return Ok(());
         }
 
-        // Fast path: process the line directly from the BufReader's internal buffer
-        // (zero copy for the common case where the full line is already buffered).
-        let (found, consumed) = {
-            let available = reader.fill_buf().context("Failed to read input")?;
-            if available.is_empty() {
-                break;
-            }
-            if let Some(nl) = available.iter().position(|&b| b == b'\n') {
-                let before_nl = &available[..nl];
-                let line = if before_nl.last() == Some(&b'\r') {
-                    &before_nl[..before_nl.len() - 1]
-                } else {
-                    before_nl
-                };
-                let start_pos = (lines_read as f64) * config.spread + config.random_offset;
-                processor.process_line(line, start_pos, config, color_mode)?;
-                lines_read += 1;
-                (true, nl + 1)
-            } else {
-                (false, 0_usize)
-            }
-        };
+        if processor.lines_read >= max_lines {
+            // Only reached with input left to drop
+            return Err(ProcessError::LineLimit(max_lines));
+        }
 
-        if found {
-            reader.consume(consumed);
-        } else {
-            // Slow path: line spans a buffer boundary — fall back to read_until
-            line_buf.clear();
-            let n = reader
-                .read_until(b'\n', &mut line_buf)
-                .context("Failed to read line")?;
-            if n == 0 {
+        // Process lines directly from the BufReader's internal buffer (zero copy),
+        // all the lines it holds before filling it again.
+        let mut consumed = 0;
+        while let Some(nl) = available[consumed..].iter().position(|&b| b == b'\n') {
+            processor.end_line(&available[consumed..consumed + nl], config, color_mode)?;
+            consumed += nl + 1;
+            if matches!(config.buffering, Buffering::Line | Buffering::Unbuffered) {
+                processor.flush()?;
+            }
+            if processor.lines_read >= max_lines {
                 break;
             }
-            let mut line_len = line_buf.len();
-            if line_buf.last() == Some(&b'\n') {
-                line_len -= 1;
-                if line_len > 0 && line_buf[line_len - 1] == b'\r' {
-                    line_len -= 1;
-                }
+        }
+        // A line that does not fit in the buffer is processed segment by segment,
+        // carrying its phase across fills, so memory stays bounded however long
+        // the line is.
+        if consumed == 0 {
+            processor.process_segment(available, config, color_mode)?;
+            consumed = available.len();
+            if config.buffering == Buffering::Unbuffered {
+                processor.flush()?;
             }
-            let start_pos = (lines_read as f64) * config.spread + config.random_offset;
-            processor.process_line(&line_buf[..line_len], start_pos, config, color_mode)?;
-            lines_read += 1;
+        }
+        let drained = consumed == available.len();
+        reader.consume(consumed);
+
+        // A drained input buffer means the next read may block, so `Auto`
+        // flushes here rather than leave output waiting on more input
+        if drained && config.buffering == Buffering::Auto {
+            processor.flush()?;
         }
     }
+}
+
+/// Color `reader` a block at a time, each around its own centre or with the
+/// rainbow fitted to it
+fn process_blocks<R: BufRead, W: Write>(
+    mut reader: R,
+    processor: &mut BatchProcessor<W>,
+    config: &Config,
+    color_mode: ColorMode,
+) -> Result<(), ProcessError> {
+    let max_lines = config.max_lines.unwrap_or(usize::MAX);
+    let (block_lines, paragraphs) = match config.fit {
+        Fit::Off => (config.block_lines, false),
+        Fit::Document => (usize::MAX, false),
+        Fit::Paragraphs => (usize::MAX, true),
+    };
+    let mut block = Vec::new();
+    let mut len = 0;
 
-    processor.finish()
+    loop {
+        if let Some(signal) = interrupted() {
+            return Err(ProcessError::Interrupted(signal));
+        }
+        // Past the line limit, a line is still read to tell whether any
+        // input was left to drop
+        let lines = block_lines
+            .min(max_lines.saturating_sub(processor.lines_read))
+            .max(1);
+        block.drain(..len);
+        len = read_block(&mut reader, lines, paragraphs, &mut block).map_err(ProcessError::Read)?;
+        if len == 0 {
+            return Ok(());
+        }
+        processor.start_block(&block[..len], config);
+        process_lines(&block[..len], processor, config, color_mode)?;
+    }
 }
 
 /// Process input from a reader, applying rainbow colors to each line, writing to a custom writer
@@ -433,16 +1233,13 @@ pub fn process_input_with_color_mode<R: BufRead, W: Write>(
 ///
 /// # Errors
 ///
-/// Returns an error if:
-/// - Reading from the input reader fails
-/// - Writing to the output writer fails
-/// - Maximum line limit is exceeded
+/// Returns the same errors as [`process_input_with_color_mode`]
 pub fn process_input_to_writer<R: BufRead, W: Write>(
     reader: R,
     writer: W,
     config: &Config,
-) -> Result<()> {
-    let color_mode = detect_color_support(config.force_color);
+) -> Result<usize, ProcessError> {
+    let color_mode = config.color_mode_for(&io::stdout());
     process_input_with_color_mode(reader, writer, config, color_mode)
 }
 
@@ -450,18 +1247,27 @@ pub fn process_input_to_writer<R: BufRead, W: Write>(
 ///
 /// # Errors
 ///
-/// Returns an error if:
-/// - Reading from the input reader fails
-/// - Writing to stdout fails
-/// - Maximum line limit is exceeded
-pub fn process_input<R: BufRead>(reader: R, config: &Config) -> Result<()> {
-    let stdout = io::stdout().lock();
+/// Returns the same errors as [`process_input_with_color_mode`], with
+/// output going to stdout
+pub fn process_input<R: BufRead>(reader: R, config: &Config) -> Result<usize, ProcessError> {
+    // Not locked for the whole run, so the signal handler can still write
+    // the reset sequence if processing is blocked
+    let stdout = io::stdout();
+    if config.buffering == Buffering::Auto && stdout.is_terminal() {
+        let config = Config {
+            buffering: Buffering::Line,
+            ..config.clone()
+        };
+        return process_input_to_writer(reader, stdout, &config);
+    }
     process_input_to_writer(reader, stdout, config)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::{ConfigError, Direction, TabStops};
+    use std::f64::consts::TAU;
     use std::io::{BufReader, Cursor};
 
     /// Strip all ANSI escape sequences from output bytes, returning plain text.
@@ -512,9 +1318,28 @@ mod tests {
         }
     }
 
-    /// Tab expansion: each \t becomes 8 spaces
+    /// Tab expansion: each \t advances to the next multiple of 8 columns
     fn expand_tabs(input: &str) -> String {
-        input.replace('\t', "        ")
+        let mut output = String::with_capacity(input.len());
+        let mut column = 0;
+        for c in input.chars() {
+            match c {
+                '\t' => {
+                    let width = 8 - column % 8;
+                    output.extend(std::iter::repeat_n(' ', width));
+                    column += width;
+                }
+                '\n' => {
+                    output.push(c);
+                    column = 0;
+                }
+                _ => {
+                    output.push(c);
+                    column += 1;
+                }
+            }
+        }
+        output
     }
 
     #[test]
@@ -601,4 +1426,717 @@ mod tests {
         let result = String::from_utf8(stripped).expect("output must be valid UTF-8");
         assert_eq!(result, expected_output(input));
     }
+
+    #[test]
+    fn invalid_config_is_reported() {
+        let mut config = Config::try_new(0.04, 4.0, true).unwrap();
+        config.frequency = f64::NAN;
+        let reader = BufReader::new(Cursor::new(b"text\n"));
+        let result =
+            process_input_with_color_mode(reader, Vec::new(), &config, ColorMode::TrueColor);
+        assert!(matches!(result, Err(ProcessError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn closed_output_is_broken_pipe() {
+        struct ClosedPipe;
+
+        impl Write for ClosedPipe {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::BrokenPipe.into())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let config = Config::try_new(0.04, 4.0, true).unwrap();
+        for color_mode in [ColorMode::TrueColor, ColorMode::NoColor] {
+            let reader = BufReader::new(Cursor::new(b"text\n"));
+            let result = process_input_with_color_mode(reader, ClosedPipe, &config, color_mode);
+            assert!(matches!(result, Err(ProcessError::BrokenPipe)));
+        }
+    }
+
+    fn process_with_limit(
+        input: &str,
+        max_lines: Option<usize>,
+        color_mode: ColorMode,
+    ) -> (String, Result<usize, ProcessError>) {
+        let mut config = Config::try_new(0.04, 4.0, true).unwrap();
+        config.max_lines = max_lines;
+        let reader = BufReader::new(Cursor::new(input.as_bytes()));
+        let mut output = Vec::new();
+        let result = process_input_with_color_mode(reader, &mut output, &config, color_mode);
+        (String::from_utf8(strip_ansi(&output)).unwrap(), result)
+    }
+
+    #[test]
+    fn line_limit_truncates_and_reports() {
+        for color_mode in [ColorMode::TrueColor, ColorMode::NoColor] {
+            let (output, result) = process_with_limit("one\ntwo\nthree\n", Some(2), color_mode);
+            assert_eq!(output, "one\ntwo\n");
+            assert!(matches!(result, Err(ProcessError::LineLimit(2))));
+        }
+    }
+
+    #[test]
+    fn line_limit_not_reached() {
+        for color_mode in [ColorMode::TrueColor, ColorMode::NoColor] {
+            let (output, result) = process_with_limit("one\ntwo\n", Some(2), color_mode);
+            assert_eq!(output, "one\ntwo\n");
+            assert!(result.is_ok());
+
+            let (output, result) = process_with_limit("one\ntwo\n", None, color_mode);
+            assert_eq!(output, "one\ntwo\n");
+            assert!(result.is_ok());
+        }
+    }
+
+    fn process_with_capacity(input: &[u8], capacity: usize, config: &Config) -> Vec<u8> {
+        let reader = BufReader::with_capacity(capacity, Cursor::new(input));
+        let mut output = Vec::new();
+        process_input_with_color_mode(reader, &mut output, config, ColorMode::TrueColor).unwrap();
+        output
+    }
+
+    #[test]
+    fn long_line_streams_in_small_buffer() {
+        let line = "abc\u{00e9}def\u{2019}ghi\u{4e16}jkl\u{1F308}mno\t".repeat(40_000);
+        let input = format!("{line}\nshort\n{line}");
+        for config in [
+            Config::try_new(0.04, 4.0, true).unwrap(),
+            Config::try_new(0.001, 10.0, true).unwrap(),
+        ] {
+            let expected = process_with_capacity(input.as_bytes(), 1 << 24, &config);
+            let output = process_with_capacity(input.as_bytes(), 16, &config);
+            assert_eq!(output, expected);
+            assert_eq!(
+                String::from_utf8(strip_ansi(&output)).unwrap(),
+                expected_output(&expand_tabs(&input))
+            );
+        }
+    }
+
+    #[test]
+    fn segment_boundaries_do_not_change_output() {
+        let input = "one\r\ntwo\r\r\n\x1b[1mbold\x1b[0m caf\u{00e9} \u{1F308}\tend\r\n\r\n\rlast\r";
+        for config in [
+            Config::try_new(0.04, 4.0, true).unwrap(),
+            Config::try_new(0.001, 10.0, true).unwrap(),
+        ] {
+            let expected = process_with_capacity(input.as_bytes(), 1024, &config);
+            for capacity in 1..8 {
+                let output = process_with_capacity(input.as_bytes(), capacity, &config);
+                assert_eq!(output, expected, "buffer capacity {capacity}");
+            }
+        }
+    }
+
+    #[test]
+    fn line_endings_preserved() {
+        let mut config = Config::try_new(0.04, 4.0, true).unwrap();
+        config.line_endings = LineEndings::Preserve;
+        // Tabs are expanded unless kept too
+        config.tabs = Tabs::Preserve;
+        for input in [
+            "one\r\ntwo\nthree",
+            "\ttabs\tkept\r\n",
+            "crlf\r\n",
+            "\r\n\r\n\n",
+            "cr\r\r\nlone\rcr\r",
+            "",
+        ] {
+            for capacity in [1, 2, 3, 1024] {
+                let output = process_with_capacity(input.as_bytes(), capacity, &config);
+                assert_eq!(strip_ansi(&output), input.as_bytes(), "{input:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn line_endings_preserved_without_color_codes_on_crlf() {
+        let mut config = Config::try_new(0.04, 4.0, true).unwrap();
+        let normalized = process_with_capacity(b"one\r\ntwo\r\n", 1024, &config);
+        config.line_endings = LineEndings::Preserve;
+        let preserved = process_with_capacity(b"one\r\ntwo\r\n", 1024, &config);
+        // The `\r` is added in front of each `\n` and nothing else changes
+        let expected: Vec<u8> = normalized
+            .iter()
+            .flat_map(|&b| {
+                if b == b'\n' {
+                    vec![b'\r', b'\n']
+                } else {
+                    vec![b]
+                }
+            })
+            .collect();
+        assert_eq!(preserved, expected);
+    }
+
+    #[test]
+    fn carriage_returns_rewind_to_line_start() {
+        let mut config = Config::try_new(0.3, 1.0, true).unwrap();
+        let input = b"abcdef\rabcdef\rab\n";
+
+        let output = process_with_capacity(input, 1024, &config);
+        let redraws: Vec<&[u8]> = output.split(|&b| b == b'\r').collect();
+        assert_ne!(redraws[0], redraws[1]);
+
+        config.carriage_returns = CarriageReturns::Rewind;
+        for capacity in [1, 2, 7, 1024] {
+            let output = process_with_capacity(input, capacity, &config);
+            assert_eq!(strip_ansi(&output), input);
+            let redraws: Vec<&[u8]> = output.split(|&b| b == b'\r').collect();
+            assert_eq!(redraws.len(), 3);
+            assert_eq!(redraws[0], redraws[1]);
+            assert!(redraws[0].starts_with(&redraws[2][..redraws[2].len() - 20]));
+        }
+    }
+
+    #[test]
+    fn escapes_to_the_first_column_rewind() {
+        let mut config = Config::try_new(0.3, 1.0, true).unwrap();
+        config.carriage_returns = CarriageReturns::Rewind;
+        let input = b"abcdef\x1b[1Gabcdef\x1b[Gab\n";
+
+        let output = process_with_capacity(input, 1024, &config);
+        assert_eq!(strip_ansi(&output), strip_ansi(input));
+        let redraws: Vec<&[u8]> = output.split(|&b| b == b'G').collect();
+        assert_eq!(redraws.len(), 3);
+        assert_eq!(
+            redraws[0].strip_suffix(b"\x1b[1"),
+            redraws[1].strip_suffix(b"\x1b[")
+        );
+
+        // Other columns are left to the terminal
+        let output = process_with_capacity(b"abcdef\x1b[4Gabcdef\n", 1024, &config);
+        let redraws: Vec<&[u8]> = output.split(|&b| b == b'G').collect();
+        let first = redraws[0].strip_suffix(b"\x1b[4").unwrap();
+        assert!(!redraws[1].starts_with(first));
+    }
+
+    #[test]
+    fn carriage_returns_flush_copied_output() {
+        let mut config = Config::try_new(0.04, 4.0, true).unwrap();
+        config.carriage_returns = CarriageReturns::Rewind;
+        config.buffering = Buffering::Block;
+        config.line_endings = LineEndings::Preserve;
+        config.tabs = Tabs::Preserve;
+        let mut flushes = Flushes::default();
+        let reader = BufReader::with_capacity(4, Cursor::new(b"10%\r50%\rdone\n"));
+        process_input_with_color_mode(reader, &mut flushes, &config, ColorMode::NoColor).unwrap();
+        assert_eq!(flushes.flushed, [&b"10%\r"[..], b"10%\r50%\r"]);
+    }
+
+    /// Records the output seen at each flush
+    #[derive(Default)]
+    struct Flushes {
+        written: Vec<u8>,
+        flushed: Vec<Vec<u8>>,
+    }
+
+    impl Write for &mut Flushes {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushed.push(strip_ansi(&self.written));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn carriage_returns_flush_output() {
+        let mut config = Config::try_new(0.04, 4.0, true).unwrap();
+        config.carriage_returns = CarriageReturns::Rewind;
+        config.buffering = Buffering::Block;
+        let mut flushes = Flushes::default();
+        let reader = BufReader::with_capacity(4, Cursor::new(b"10%\r50%\rdone\n"));
+        process_input_with_color_mode(reader, &mut flushes, &config, ColorMode::TrueColor).unwrap();
+        assert_eq!(
+            flushes.flushed,
+            [
+                &b"10%"[..],
+                b"10%\r",
+                b"10%\r50%",
+                b"10%\r50%\r",
+                b"10%\r50%\rdone\n"
+            ]
+        );
+    }
+
+    fn flushes_with(buffering: Buffering, color_mode: ColorMode) -> Vec<Vec<u8>> {
+        let mut config = Config::try_new(0.04, 4.0, true).unwrap();
+        config.buffering = buffering;
+        if color_mode == ColorMode::NoColor {
+            // Copied as it is
+            config.line_endings = LineEndings::Preserve;
+            config.tabs = Tabs::Preserve;
+        }
+        let mut flushes = Flushes::default();
+        let reader = BufReader::with_capacity(8, Cursor::new(b"one\ntwo\nthree"));
+        process_input_with_color_mode(reader, &mut flushes, &config, color_mode).unwrap();
+        flushes.flushed
+    }
+
+    #[test]
+    fn buffering_modes_flush_output() {
+        let color_mode = ColorMode::TrueColor;
+        assert_eq!(
+            flushes_with(Buffering::Block, color_mode),
+            [b"one\ntwo\nthree\n"]
+        );
+        assert_eq!(
+            flushes_with(Buffering::Auto, color_mode),
+            [&b"one\ntwo\n"[..], b"one\ntwo\nthree", b"one\ntwo\nthree\n"]
+        );
+        assert_eq!(
+            flushes_with(Buffering::Line, color_mode),
+            [&b"one\n"[..], b"one\ntwo\n", b"one\ntwo\nthree\n"]
+        );
+        assert_eq!(
+            flushes_with(Buffering::Unbuffered, color_mode),
+            [
+                &b"one\n"[..],
+                b"one\ntwo\n",
+                b"one\ntwo\nthree",
+                b"one\ntwo\nthree\n"
+            ]
+        );
+    }
+
+    #[test]
+    fn lolcat_direction_matches_lolcat_phases() {
+        // lolcat colors character `i` of a line at `freq * (os + i / spread)`,
+        // moving `os` along by one before each line
+        let (frequency, spread, os) = (1.0, 2.5, 5.0);
+        let lolcat = |line: usize, i: usize| {
+            let pos = frequency * (os + 1.0 + line as f64 + i as f64 / spread);
+            [0.0, 1.0, 2.0].map(|third| (pos + third * TAU / 3.0).sin() * 127.0 + 128.0)
+        };
+
+        let mut config = Config::try_new(frequency, spread, true).unwrap();
+        config.direction = Direction::Lolcat;
+        config.set_offset(os + 1.0);
+        let output = process_with_capacity(b"abcdef\nabcdef\nabcdef\n", 1024, &config);
+        let output = String::from_utf8(output).unwrap();
+        for (line, text) in output.lines().take(3).enumerate() {
+            // Every character gets a color of its own at this frequency
+            let colors: Vec<&str> = text.split("\x1b[38;2;").skip(1).collect();
+            assert_eq!(colors.len(), 6, "{text:?}");
+            for (i, color) in colors.iter().enumerate() {
+                let rgb: Vec<f64> = color[..color.find('m').unwrap()]
+                    .split(';')
+                    .map(|channel| channel.parse().unwrap())
+                    .collect();
+                for (channel, expected) in rgb.iter().zip(lolcat(line, i)) {
+                    assert!(
+                        (channel - expected).abs() < 2.0,
+                        "line {line}, character {i}: {rgb:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn uncolored_text_is_still_normalized() {
+        let mut config = Config::try_new(0.04, 4.0, true).unwrap();
+        let input = b"one\r\n\x1b[1mab\tc\x1b[0m";
+        let uncolored = |config: &Config, capacity| {
+            let reader = BufReader::with_capacity(capacity, Cursor::new(input));
+            let mut output = Vec::new();
+            process_input_with_color_mode(reader, &mut output, config, ColorMode::NoColor).unwrap();
+            output
+        };
+        for capacity in [1, 2, 3, 1024] {
+            assert_eq!(
+                uncolored(&config, capacity),
+                b"one\n\x1b[1mab      c\x1b[0m\n",
+                "capacity {capacity}"
+            );
+        }
+
+        config.line_endings = LineEndings::Preserve;
+        config.tabs = Tabs::Preserve;
+        assert_eq!(uncolored(&config, 1024), input);
+    }
+
+    #[test]
+    fn buffering_without_color_flushes_each_read() {
+        assert_eq!(
+            flushes_with(Buffering::Auto, ColorMode::NoColor),
+            [&b"one\ntwo\n"[..], b"one\ntwo\nthree"]
+        );
+        assert!(flushes_with(Buffering::Block, ColorMode::NoColor).is_empty());
+    }
+
+    fn process_tabs(input: &str, tab_stops: TabStops, tabs: Tabs) -> Vec<u8> {
+        let mut config = Config::try_new(0.3, 1.0, true).unwrap();
+        config.tab_stops = tab_stops;
+        config.tabs = tabs;
+        process_with_capacity(input.as_bytes(), 1024, &config)
+    }
+
+    #[test]
+    fn tabs_expand_to_tab_stops() {
+        let output = process_tabs("a\tbc\tdef\tg\n\tx", TabStops::Every(4), Tabs::Expand);
+        assert_eq!(strip_ansi(&output), b"a   bc  def g\n    x\n");
+
+        let output = process_tabs("\ta\tbcd\te\tf", TabStops::List(vec![2, 5]), Tabs::Expand);
+        assert_eq!(strip_ansi(&output), b"  a  bcd e f\n");
+    }
+
+    #[test]
+    fn preserved_tabs_advance_phase() {
+        /// The color sequence written for the last `x`
+        fn color_of_x(output: &[u8]) -> &[u8] {
+            let x = output.iter().rposition(|&b| b == b'x').unwrap();
+            let esc = output[..x].iter().rposition(|&b| b == 0x1b).unwrap();
+            &output[esc..x]
+        }
+
+        let input = "caf\u{00e9}\tx";
+        let expanded = process_tabs(input, TabStops::Every(8), Tabs::Expand);
+        let preserved = process_tabs(input, TabStops::Every(8), Tabs::Preserve);
+        assert_eq!(strip_ansi(&preserved), format!("{input}\n").as_bytes());
+        assert_eq!(color_of_x(&preserved), color_of_x(&expanded));
+    }
+
+    #[test]
+    fn invalid_tab_stops_are_reported() {
+        for tab_stops in [
+            TabStops::Every(0),
+            TabStops::List(vec![]),
+            TabStops::List(vec![4, 2]),
+        ] {
+            let mut config = Config::try_new(0.04, 4.0, true).unwrap();
+            config.tab_stops = tab_stops;
+            let reader = BufReader::new(Cursor::new(b"\t\n"));
+            let result =
+                process_input_with_color_mode(reader, Vec::new(), &config, ColorMode::TrueColor);
+            assert!(matches!(
+                result,
+                Err(ProcessError::InvalidConfig(ConfigError::InvalidTabStops(_)))
+            ));
+        }
+    }
+
+    #[test]
+    fn set_offset_moves_the_rainbow() {
+        let mut config = Config::try_new(0.04, 4.0, true).unwrap();
+        config.set_offset(0.0);
+        let at_zero = process_with_capacity(b"colors\n", 1024, &config);
+        config.set_offset(42.0);
+        assert_ne!(process_with_capacity(b"colors\n", 1024, &config), at_zero);
+        config.set_offset(0.0);
+        assert_eq!(process_with_capacity(b"colors\n", 1024, &config), at_zero);
+
+        config.set_offset(f64::NAN);
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::InvalidOffset(offset)) if offset.is_nan()
+        ));
+    }
+
+    #[test]
+    fn direction_changes_what_advances_the_rainbow() {
+        let input = b"rainbow colors\ncaf\xc3\xa9 colors\n";
+        let mut config = Config::try_new(0.5, 2.0, true).unwrap();
+        config.set_offset(0.0);
+        let mut output = |direction| {
+            config.direction = direction;
+            process_with_capacity(input, 1024, &config)
+        };
+        let colors = |line: &[u8]| line.windows(7).filter(|w| w == b"\x1b[38;2;").count();
+
+        // Every line starts with the same color
+        let horizontal = output(Direction::Horizontal);
+        let lines: Vec<_> = horizontal.split(|&b| b == b'\n').collect();
+        assert_eq!(lines[0][..19], lines[1][..19]);
+        assert!(colors(lines[0]) > 1);
+
+        // Every line is a single color, a different one for each line
+        let vertical = output(Direction::Vertical);
+        let lines: Vec<_> = vertical.split(|&b| b == b'\n').collect();
+        assert_eq!((colors(lines[0]), colors(lines[1])), (1, 1));
+        assert_ne!(lines[0][..19], lines[1][..19]);
+
+        assert_eq!(output(Direction::Angle(0.0)), horizontal);
+        assert_eq!(output(Direction::Angle(450.0)), vertical);
+        assert_ne!(output(Direction::Angle(180.0)), horizontal);
+        assert_eq!(
+            strip_ansi(&output(Direction::Angle(-135.0))),
+            strip_ansi(input)
+        );
+
+        config.direction = Direction::Angle(f64::INFINITY);
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::InvalidAngle(angle)) if angle.is_infinite()
+        ));
+    }
+
+    #[test]
+    fn steps_of_whole_turns_make_no_difference() {
+        let input = b"rainbow colors\ncaf\xc3\xa9 colors\n";
+        let output = |spread| {
+            let mut config = Config::try_new(TAU, spread, true).unwrap();
+            config.direction = Direction::Horizontal;
+            config.set_offset(0.0);
+            process_with_capacity(input, 1024, &config)
+        };
+        // Half a turn per column, then two and a half
+        assert_eq!(output(0.4), output(2.0));
+    }
+
+    #[test]
+    fn centred_directions_color_around_each_block() {
+        let input = "abcde\n".repeat(6);
+        let mut config = Config::try_new(0.5, 2.0, true).unwrap();
+        config.set_offset(0.0);
+        config.block_lines = 3;
+        let output = |config: &Config| {
+            let output = process_with_capacity(input.as_bytes(), 1024, config);
+            assert_eq!(process_with_capacity(input.as_bytes(), 3, config), output);
+            assert_eq!(strip_ansi(&output), input.as_bytes());
+            output
+        };
+
+        // Mirrored about the middle line and column, and again in the next block
+        config.direction = Direction::Radial;
+        let radial = output(&config);
+        let lines: Vec<_> = radial.split(|&b| b == b'\n').collect();
+        assert_eq!(lines[0], lines[2]);
+        assert_ne!(lines[0], lines[1]);
+        assert_eq!(lines[3..6], lines[0..3]);
+        assert_eq!(lines[0][..19], lines[0][lines[0].len() - 20..][..19]);
+
+        // Opposite sides of the centre are half a rainbow apart
+        config.direction = Direction::Conical;
+        let conical = output(&config);
+        let lines: Vec<_> = conical.split(|&b| b == b'\n').collect();
+        assert_ne!(lines[1][..19], lines[1][lines[1].len() - 20..][..19]);
+        assert_ne!(lines[0][..19], lines[2][..19]);
+
+        config.max_lines = Some(4);
+        let mut truncated = Vec::new();
+        let result = process_input_with_color_mode(
+            input.as_bytes(),
+            &mut truncated,
+            &config,
+            ColorMode::TrueColor,
+        );
+        assert!(matches!(result, Err(ProcessError::LineLimit(4))));
+        assert_eq!(strip_ansi(&truncated), "abcde\n".repeat(4).as_bytes());
+
+        config.block_lines = 0;
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::InvalidBlockLines(0))
+        ));
+    }
+
+    #[test]
+    fn fit_spans_one_rainbow() {
+        let mut config = Config::try_new(0.5, 2.0, true).unwrap();
+        config.set_offset(0.0);
+        config.direction = Direction::Horizontal;
+        let output = |input: &str, config: &Config| {
+            let output = process_with_capacity(input.as_bytes(), 1024, config);
+            output
+                .split(|&b| b == b'\n')
+                .map(<[u8]>::to_vec)
+                .collect::<Vec<_>>()
+        };
+        let base = config.clone();
+        let unfitted = |input: &str, frequency: f64| {
+            let config = Config {
+                frequency,
+                ..base.clone()
+            };
+            output(input, &config)
+        };
+
+        // The widest line is 8 columns of half a spread
+        config.fit = Fit::Document;
+        assert_eq!(
+            output("abcdefgh\nabcd\n", &config),
+            unfitted("abcdefgh\nabcd\n", TAU / 4.0)
+        );
+
+        // Each paragraph on its own
+        config.fit = Fit::Paragraphs;
+        let lines = output("abcdefgh\n\nabcd\n", &config);
+        assert_eq!(lines[0], unfitted("abcdefgh\n", TAU / 4.0)[0]);
+        assert_eq!(lines[2], unfitted("abcd\n", TAU / 2.0)[0]);
+    }
+
+    #[test]
+    fn line_count_is_returned() {
+        for color_mode in [ColorMode::TrueColor, ColorMode::NoColor] {
+            for (input, lines) in [("", 0), ("one\ntwo\n", 2), ("one\ntwo", 2), ("\n\n\n", 3)] {
+                let (_, result) = process_with_limit(input, None, color_mode);
+                assert_eq!(result.unwrap(), lines, "{input:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn first_line_continues_rainbow() {
+        const RESET: &[u8] = b"\x1b[0m\x1b[39m\x1b[49m";
+        let mut config = Config::try_new(0.2, 2.0, true).unwrap();
+        let whole = process_with_capacity(b"one\ntwo\nthree\n", 1024, &config);
+
+        let mut first = process_with_capacity(b"one\ntwo\n", 1024, &config);
+        config.first_line = 2;
+        let second = process_with_capacity(b"three\n", 1024, &config);
+        first.truncate(first.len() - RESET.len());
+        first.extend_from_slice(&second);
+        assert_eq!(first, whole);
+    }
+
+    #[test]
+    fn background_layer_resets_before_line_end() {
+        let mut config = Config::try_new(0.3, 1.0, true).unwrap();
+        let foreground = process_with_capacity(b"ab\ncd\n", 1024, &config);
+        config.layer = Layer::Background;
+        let background = process_with_capacity(b"ab\ncd\n", 1024, &config);
+        assert!(!background.windows(5).any(|w| w == b"\x1b[38;"));
+        assert_eq!(
+            String::from_utf8(background).unwrap(),
+            String::from_utf8(foreground)
+                .unwrap()
+                .replace("\x1b[38;", "\x1b[48;")
+                .replace('\n', "\x1b[49m\n")
+        );
+    }
+
+    fn process_decorated(
+        input: &[u8],
+        decorations: Decorations,
+        tabs: Tabs,
+        color_mode: ColorMode,
+    ) -> Vec<u8> {
+        let mut config = Config::try_new(0.3, 1.0, true).unwrap();
+        config.decorations = decorations;
+        config.tabs = tabs;
+        let expected = {
+            let mut output = Vec::new();
+            process_input_with_color_mode(input, &mut output, &config, color_mode).unwrap();
+            output
+        };
+        for capacity in [1, 2, 3, 7] {
+            let reader = BufReader::with_capacity(capacity, Cursor::new(input));
+            let mut output = Vec::new();
+            process_input_with_color_mode(reader, &mut output, &config, color_mode).unwrap();
+            assert_eq!(output, expected, "capacity {capacity}");
+        }
+        expected
+    }
+
+    #[test]
+    fn decorations_match_cat() {
+        let numbered = |line_numbers| Decorations {
+            line_numbers,
+            ..Decorations::default()
+        };
+        let input = b"a\n\nb\tc\n\n\n\nd";
+        let cases: [(Decorations, &[u8]); 5] = [
+            (
+                numbered(LineNumbers::All),
+                b"     1\ta\n     2\t\n     3\tb\tc\n     4\t\n     5\t\n     6\t\n     7\td\n",
+            ),
+            (
+                numbered(LineNumbers::NonBlank),
+                b"     1\ta\n\n     2\tb\tc\n\n\n\n     3\td\n",
+            ),
+            (
+                Decorations {
+                    squeeze_blank: true,
+                    line_numbers: LineNumbers::All,
+                    ..Decorations::default()
+                },
+                b"     1\ta\n     2\t\n     3\tb\tc\n     4\t\n     5\td\n",
+            ),
+            (
+                Decorations {
+                    show_ends: true,
+                    show_tabs: true,
+                    ..Decorations::default()
+                },
+                b"a$\n$\nb^Ic$\n$\n$\n$\nd$\n",
+            ),
+            (
+                Decorations {
+                    show_nonprinting: true,
+                    ..Decorations::default()
+                },
+                b"a\n\nb\tc\n\n\n\nd\n",
+            ),
+        ];
+        for (decorations, expected) in cases {
+            // With tabs kept, like `cat`
+            let output = process_decorated(input, decorations, Tabs::Preserve, ColorMode::NoColor);
+            assert_eq!(output, expected, "{decorations:?}");
+        }
+    }
+
+    #[test]
+    fn squeezed_lines_keep_their_place() {
+        let input = b"a\n\n\n\nbc\n";
+        let last_line = |squeeze_blank| {
+            let mut config = Config::try_new(0.3, 1.0, true).unwrap();
+            config.direction = Direction::Radial;
+            config.decorations.squeeze_blank = squeeze_blank;
+            let mut output = Vec::new();
+            let lines = process_input_with_color_mode(
+                &input[..],
+                &mut output,
+                &config,
+                ColorMode::TrueColor,
+            )
+            .unwrap();
+            assert_eq!(lines, 5);
+            let lines: Vec<_> = output.split(|&b| b == b'\n').map(<[u8]>::to_vec).collect();
+            lines[lines.len() - 2].clone()
+        };
+        assert_eq!(last_line(true), last_line(false));
+    }
+
+    #[test]
+    fn nonprinting_characters_are_shown() {
+        let decorations = Decorations {
+            show_nonprinting: true,
+            show_ends: true,
+            show_tabs: true,
+            ..Decorations::default()
+        };
+        let output = process_decorated(
+            b"\x1b[1mbold\x7f\t\xc3\xa9\x89\r\r\n",
+            decorations,
+            Tabs::Expand,
+            ColorMode::NoColor,
+        );
+        assert_eq!(output, b"^[[1mbold^?^IM-CM-)M-^I^M^M$\n");
+    }
+
+    #[test]
+    fn decorations_are_colored() {
+        let decorations = Decorations {
+            line_numbers: LineNumbers::All,
+            show_ends: true,
+            ..Decorations::default()
+        };
+        let output = process_decorated(b"ab\n", decorations, Tabs::Expand, ColorMode::TrueColor);
+        assert_eq!(strip_ansi(&output), b"     1  ab$\n");
+        // At this frequency every column, decorations included, gets a color
+        // of its own
+        let colors = output.windows(7).filter(|w| w == b"\x1b[38;2;").count();
+        assert_eq!(colors, "     1  ab$".len());
+    }
 }
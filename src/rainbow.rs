@@ -4,6 +4,11 @@ use crate::color::Color;
 const TABLE_SIZE: usize = 2048;
 const MASK: usize = TABLE_SIZE - 1; // For fast power-of-2 wrapping
 
+// Fixed-point phases: the table index is in the bits above FP_SHIFT
+const FP_SHIFT: u32 = 32;
+// One full turn of the table in fixed point
+const PHASE_PERIOD: u64 = (TABLE_SIZE as u64) << FP_SHIFT;
+
 // Include the pre-computed tables generated at build time
 include!(concat!(env!("OUT_DIR"), "/rainbow_tables.rs"));
 
@@ -70,14 +75,29 @@ impl RainbowLookup {
     /// Returns (`initial_phase`, `phase_increment`) for integer-only per-glyph updates.
     ///
     /// Use this to eliminate all floating-point math in hot loops.
+    /// Both values are wrapped to within one turn of the table, so a
+    /// gradient running backwards works with the same wrapping additions,
+    /// and a step of more than a turn is not mistaken for one backwards.
     #[must_use]
     pub fn fixedpoint_phase(&self, start_pos: f64, pos_increment: f64) -> (u64, u64) {
-        const FP_SHIFT: u32 = 32;
+        // Largest magnitude that converts to an `i64` without saturating
+        const I64_RANGE: f64 = 9_223_372_036_854_775_808.0;
+
         let s = self.scale * ((1u64 << FP_SHIFT) as f64);
+        // Only the bits up to the table index matter, and a turn is a power
+        // of two, so masking takes values modulo a turn; `fmod` is too slow
+        // for every line and only needed past the range of an `i64`
+        let wrap = |fixed: f64| {
+            if fixed.abs() < I64_RANGE {
+                (fixed as i64 as u64) & (PHASE_PERIOD - 1)
+            } else {
+                fixed.rem_euclid(PHASE_PERIOD as f64) as u64
+            }
+        };
 
-        let phase0 = (start_pos * s) as u64;
+        let phase0 = wrap(start_pos * s);
 
-        let phase_inc = (pos_increment * s) as u64;
+        let phase_inc = wrap(pos_increment * s);
 
         (phase0, phase_inc)
     }
@@ -101,14 +121,33 @@ impl RainbowLookup {
         ((phase >> 32) as usize) & MASK
     }
 
+    /// Whether `phase_inc` (from [`fixedpoint_phase`](Self::fixedpoint_phase))
+    /// moves less than a sixteenth of a color per glyph, in either direction,
+    /// so glyphs share colors in runs worth batching with
+    /// [`run_len_until_next_index`](Self::run_len_until_next_index)
+    #[inline(always)]
+    #[must_use]
+    pub fn changes_slowly(&self, phase_inc: u64) -> bool {
+        let phase_inc = phase_inc & (PHASE_PERIOD - 1);
+        phase_inc.min(PHASE_PERIOD - phase_inc) < (1 << (FP_SHIFT - 4))
+    }
+
     /// Calculate how many glyphs until the color index changes.
     /// Useful for batching identical color runs.
     #[inline(always)]
     #[must_use]
     pub fn run_len_until_next_index(&self, phase: u64, phase_inc: u64) -> usize {
+        // Whole turns of the table make no difference to the colors
+        let phase_inc = phase_inc & (PHASE_PERIOD - 1);
         if phase_inc == 0 {
             return usize::MAX;
         }
+        // More than half a turn forward is a step backward
+        if phase_inc > PHASE_PERIOD / 2 {
+            let step_back = PHASE_PERIOD - phase_inc;
+            let into_index = phase & ((1 << FP_SHIFT) - 1);
+            return (into_index / step_back) as usize + 1;
+        }
 
         let hi = phase >> 32;
         let hi_next_boundary = (hi + 1) << 32;
@@ -118,3 +157,40 @@ impl RainbowLookup {
         (delta.saturating_add(phase_inc - 1) / phase_inc) as usize
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whole_turns_wrap() {
+        let lookup = RainbowLookup::new(std::f64::consts::TAU);
+        // One position is a turn of the table
+        let (phase, phase_inc) = lookup.fixedpoint_phase(1e12 + 0.25, 2.5);
+        assert_eq!(lookup.color_index_from_phase(phase), TABLE_SIZE / 4);
+        assert_eq!(phase_inc, PHASE_PERIOD / 2);
+        let (phase, phase_inc) = lookup.fixedpoint_phase(-0.25, -1.0 / 4096.0);
+        assert_eq!(lookup.color_index_from_phase(phase), TABLE_SIZE * 3 / 4);
+        assert_eq!(phase_inc, PHASE_PERIOD - (1 << (FP_SHIFT - 1)));
+    }
+
+    #[test]
+    fn runs_are_counted_both_ways() {
+        let lookup = RainbowLookup::new(1.0);
+        let step = 1 << (FP_SHIFT - 5);
+        let phase = (5 << FP_SHIFT) + 3 * step;
+        assert!(lookup.changes_slowly(step));
+        assert!(lookup.changes_slowly(PHASE_PERIOD - step));
+        assert!(!lookup.changes_slowly(PHASE_PERIOD / 2));
+        // Forward to the next index, or back past the start of this one
+        assert_eq!(lookup.run_len_until_next_index(phase, step), 32 - 3);
+        assert_eq!(
+            lookup.run_len_until_next_index(phase, PHASE_PERIOD - step),
+            4
+        );
+        assert_eq!(
+            lookup.run_len_until_next_index(phase, 3 * PHASE_PERIOD - step),
+            4
+        );
+    }
+}
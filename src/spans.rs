@@ -0,0 +1,253 @@
+use std::iter::FusedIterator;
+
+use crate::ansi::MAX_ANSI_SEQUENCE_LENGTH;
+use crate::color::Color;
+use crate::config::{Config, TabStops};
+use crate::rainbow::RainbowLookup;
+
+impl Config {
+    /// Iterate over runs of `text` that share a rainbow color.
+    ///
+    /// Yields `(Color, &str)` pairs without writing any escape sequences,
+    /// using the same per-line phase as `process_input`. Concatenating the
+    /// yielded slices gives back `text` unchanged:
+    /// - a line ending (`\n` or `\r\n`) closes the run it ends, and the next
+    ///   line starts a new run
+    /// - a tab stays a single `\t` but advances the phase to the next tab stop
+    /// - ANSI escape sequences are kept inside the current run and do not
+    ///   advance the phase
+    #[must_use]
+    pub fn spans<'a>(&self, text: &'a str) -> Spans<'a> {
+        let lookup = RainbowLookup::new(self.frequency);
+        let pos_increment = self.column_step();
+        let first_line_pos = self.line_start_pos(0);
+        let (phase, phase_inc) = lookup.fixedpoint_phase(first_line_pos, pos_increment);
+        Spans {
+            text,
+            pos: 0,
+            lookup,
+            line_step: self.line_step(),
+            first_line_pos,
+            pos_increment,
+            lines_read: 0,
+            phase,
+            phase_inc,
+            tab_stops: self.tab_stops.clone(),
+            column: 0,
+            split_tabs: false,
+            tab: None,
+        }
+    }
+}
+
+/// Iterator over `(Color, &str)` runs, created by [`Config::spans`]
+pub struct Spans<'a> {
+    text: &'a str,
+    pos: usize,
+    lookup: RainbowLookup,
+    line_step: f64,
+    /// Rainbow position at the start of the first line
+    first_line_pos: f64,
+    pos_increment: f64,
+    lines_read: usize,
+    phase: u64,
+    phase_inc: u64,
+    tab_stops: TabStops,
+    /// Columns on the current line so far, for tab stops
+    column: usize,
+    /// Whether each tab is a run of its own
+    split_tabs: bool,
+    /// Phase and width of the tab just yielded, with `split_tabs`
+    tab: Option<(u64, usize)>,
+}
+
+impl Spans<'_> {
+    /// Yield each tab as a run of its own, for [`Spans::tab_colors`]
+    #[cfg(any(feature = "ratatui", feature = "crossterm"))]
+    pub(crate) fn split_tabs(mut self) -> Self {
+        self.split_tabs = true;
+        self
+    }
+
+    /// Colors of the cells a tab expands to, if the run just yielded is a
+    /// tab split off by [`Spans::split_tabs`]
+    #[cfg(any(feature = "ratatui", feature = "crossterm"))]
+    pub(crate) fn tab_colors(&self) -> impl Iterator<Item = Color> + '_ {
+        let (phase, width) = self.tab.unwrap_or((0, 0));
+        (0..width as u64).map(move |cell| {
+            let phase = phase.wrapping_add(self.phase_inc.wrapping_mul(cell));
+            self.lookup.color_from_phase(phase).0
+        })
+    }
+
+    fn start_next_line(&mut self) {
+        self.lines_read += 1;
+        let start_pos = (self.lines_read as f64) * self.line_step + self.first_line_pos;
+        self.phase = self
+            .lookup
+            .fixedpoint_phase(start_pos, self.pos_increment)
+            .0;
+        self.column = 0;
+    }
+
+    /// Length in bytes of the escape sequence at `start`, stopping before
+    /// any line ending
+    pub(crate) fn escape_len(bytes: &[u8], start: usize) -> usize {
+        let mut i = start + 1;
+        let mut count = 0;
+        while i < bytes.len() && count < MAX_ANSI_SEQUENCE_LENGTH && bytes[i] != b'\n' {
+            let b = bytes[i];
+            i += 1;
+            count += 1;
+            if b.is_ascii_alphabetic() {
+                break;
+            }
+        }
+        i - start
+    }
+}
+
+impl<'a> Iterator for Spans<'a> {
+    type Item = (Color, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let bytes = self.text.as_bytes();
+        let start = self.pos;
+        self.tab = None;
+        if start >= bytes.len() {
+            return None;
+        }
+
+        let (color, color_idx) = self.lookup.color_from_phase(self.phase);
+        let mut remaining = self
+            .lookup
+            .run_len_until_next_index(self.phase, self.phase_inc);
+        let mut i = start;
+
+        while i < bytes.len() {
+            let b = bytes[i];
+            match b {
+                b'\n' => {
+                    i += 1;
+                    self.start_next_line();
+                    break;
+                }
+                b'\r' if bytes.get(i + 1) == Some(&b'\n') => {
+                    i += 2;
+                    self.start_next_line();
+                    break;
+                }
+                0x1b => i += Self::escape_len(bytes, i),
+                // Continuation bytes belong to the codepoint already counted
+                0x80..0xC0 => i += 1,
+                _ => {
+                    if remaining == 0 || (b == b'\t' && self.split_tabs && i > start) {
+                        break;
+                    }
+                    i += 1;
+                    if b == b'\t' {
+                        let width = self.tab_stops.width_at(self.column);
+                        self.column += width;
+                        if self.split_tabs {
+                            self.tab = Some((self.phase, width));
+                            self.phase = self
+                                .phase
+                                .wrapping_add(self.phase_inc.wrapping_mul(width as u64));
+                            break;
+                        }
+                        self.phase = self
+                            .phase
+                            .wrapping_add(self.phase_inc.wrapping_mul(width as u64));
+                        remaining = if self.lookup.color_index_from_phase(self.phase) == color_idx {
+                            self.lookup
+                                .run_len_until_next_index(self.phase, self.phase_inc)
+                        } else {
+                            0
+                        };
+                    } else {
+                        self.phase = self.phase.wrapping_add(self.phase_inc);
+                        self.column += 1;
+                        remaining -= 1;
+                    }
+                }
+            }
+        }
+
+        self.pos = i;
+        Some((color, &self.text[start..i]))
+    }
+}
+
+impl FusedIterator for Spans<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::ColorMode;
+    use crate::writer::RainbowWriter;
+    use std::io::Write;
+
+    #[test]
+    fn spans_cover_input() {
+        let config = Config::try_new(0.3, 2.0, false).unwrap();
+        let input = "first line\r\n\tcaf\u{00e9} \x1b[1m\u{1F308}\x1b[0m\n\nlast";
+        let joined: String = config.spans(input).map(|(_, text)| text).collect();
+        assert_eq!(joined, input);
+    }
+
+    #[test]
+    fn lines_start_new_spans() {
+        let mut config = Config::try_new(0.001, 100.0, false).unwrap();
+        // A random offset can put a color boundary inside a line
+        config.random_offset = 0.0;
+        let spans: Vec<_> = config.spans("one\ntwo\n").map(|(_, text)| text).collect();
+        assert_eq!(spans, ["one\n", "two\n"]);
+    }
+
+    #[test]
+    fn steps_of_more_than_a_turn() {
+        // Over 2π apart, so each column is more than a full rainbow along
+        let config = Config::try_new(1.0, 0.001, true).unwrap();
+        let joined: String = config
+            .spans("hello world\n")
+            .map(|(_, text)| text)
+            .collect();
+        assert_eq!(joined, "hello world\n");
+    }
+
+    #[test]
+    fn invalid_tab_stops_advance_a_column() {
+        for tab_stops in [
+            TabStops::Every(0),
+            TabStops::List(vec![]),
+            TabStops::List(vec![4, 2]),
+        ] {
+            let mut config = Config::try_new(0.3, 1.0, true).unwrap();
+            config.tab_stops = tab_stops;
+            let input = "a\tbcde\tf";
+            let joined: String = config.spans(input).map(|(_, text)| text).collect();
+            assert_eq!(joined, input);
+
+            let mut writer =
+                RainbowWriter::with_color_mode(Vec::new(), &config, ColorMode::TrueColor);
+            writer.write_all(input.as_bytes()).unwrap();
+            writer.finish().unwrap();
+        }
+    }
+
+    #[test]
+    fn colors_match_rainbow_writer() {
+        let config = Config::try_new(0.2, 3.0, true).unwrap();
+        let input = "The quick brown fox jumps over the lazy dog.";
+
+        let mut expected = Vec::new();
+        for (Color(r, g, b), text) in config.spans(input) {
+            write!(expected, "\x1b[38;2;{r:03};{g:03};{b:03}m{text}").unwrap();
+        }
+        expected.extend_from_slice(b"\x1b[0m\x1b[39m\x1b[49m");
+
+        let mut writer = RainbowWriter::with_color_mode(Vec::new(), &config, ColorMode::TrueColor);
+        writer.write_all(input.as_bytes()).unwrap();
+        assert_eq!(writer.finish().unwrap(), expected);
+    }
+}
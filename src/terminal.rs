@@ -1,18 +1,126 @@
-use anyhow::Result;
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Write};
+#[cfg(unix)]
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock};
+#[cfg(unix)]
+use std::sync::{Mutex, PoisonError};
+
+/// Number of the signal that asked processing to stop, or 0. Shared with
+/// the signal handlers, which only set it.
+static INTERRUPTED: LazyLock<Arc<AtomicUsize>> = LazyLock::new(Arc::default);
+
+/// Whether signals take their default action, as they do while no
+/// [`InterruptGuard`] is live. Shared with the signal handlers.
+#[cfg(unix)]
+static DEFAULT_ACTIONS: LazyLock<Arc<AtomicBool>> =
+    LazyLock::new(|| Arc::new(AtomicBool::new(true)));
+
+/// Number of live [`InterruptGuard`]s
+#[cfg(unix)]
+static GUARDS: Mutex<usize> = Mutex::new(0);
 
 /// Ensure terminal is reset on program exit
+///
+/// Installs a panic hook that resets the terminal before running the
+/// previously installed hook. Signals are left alone; see
+/// [`handle_interrupts`] for stopping processing on them instead.
 pub fn setup_terminal_cleanup() {
-    // Set up a cleanup function that will run on program exit
-    std::panic::set_hook(Box::new(|_| {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
         let _ = reset_terminal();
+        previous(info);
     }));
 }
 
-/// Reset terminal to clean state
-pub(crate) fn reset_terminal() -> Result<()> {
+/// Stops processing on signals until dropped
+///
+/// Returned by [`handle_interrupts`]. Once the last guard is dropped, the
+/// signals end the process again and a signal received so far is
+/// forgotten, so later processing is not stopped by it.
+#[must_use = "signals are handled only until the guard is dropped"]
+#[derive(Debug)]
+pub struct InterruptGuard {
+    _private: (),
+}
+
+/// Stop processing on SIGINT, SIGTERM and SIGHUP instead of ending the
+/// process, while the returned guard lives
+///
+/// A signal then stops processing at the next read, flushing the output and
+/// writing the reset sequence, and processing returns
+/// [`ProcessError::Interrupted`](crate::ProcessError::Interrupted). It is up
+/// to the caller to exit then, for example by re-raising the signal with its
+/// default action. Processing blocked on a read only notices the signal once
+/// the read returns. Does nothing on platforms other than Unix.
+///
+/// # Errors
+///
+/// Returns an error if the signal handlers cannot be installed
+pub fn handle_interrupts() -> io::Result<InterruptGuard> {
+    #[cfg(unix)]
+    {
+        let mut guards = GUARDS.lock().unwrap_or_else(PoisonError::into_inner);
+        if *guards == 0 {
+            install_handlers()?;
+            DEFAULT_ACTIONS.store(false, Ordering::SeqCst);
+        }
+        *guards += 1;
+    }
+    Ok(InterruptGuard { _private: () })
+}
+
+impl Drop for InterruptGuard {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        {
+            let mut guards = GUARDS.lock().unwrap_or_else(PoisonError::into_inner);
+            *guards -= 1;
+            if *guards == 0 {
+                DEFAULT_ACTIONS.store(true, Ordering::SeqCst);
+                INTERRUPTED.store(0, Ordering::SeqCst);
+            }
+        }
+    }
+}
+
+/// Record SIGINT, SIGTERM and SIGHUP for processing to stop at, or take
+/// their default action while no guard is live. Installed once: handlers
+/// cannot be removed without leaving the signals ignored.
+#[cfg(unix)]
+fn install_handlers() -> io::Result<()> {
+    use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+
+    static INSTALLED: Mutex<bool> = Mutex::new(false);
+    let mut installed = INSTALLED.lock().unwrap_or_else(PoisonError::into_inner);
+    if !*installed {
+        for signal in [SIGINT, SIGTERM, SIGHUP] {
+            let number = usize::try_from(signal).expect("signal numbers are positive");
+            signal_hook::flag::register_usize(signal, Arc::clone(&INTERRUPTED), number)?;
+            signal_hook::flag::register_conditional_default(signal, Arc::clone(&DEFAULT_ACTIONS))?;
+        }
+        *installed = true;
+    }
+    Ok(())
+}
+
+/// The signal that asked processing to stop, if any
+#[inline]
+pub(crate) fn interrupted() -> Option<i32> {
+    match INTERRUPTED.load(Ordering::Relaxed) {
+        0 => None,
+        signal => i32::try_from(signal).ok(),
+    }
+}
+
+/// Reset terminal to clean state, if stdout is a terminal
+pub(crate) fn reset_terminal() -> io::Result<()> {
     let mut stdout = io::stdout();
-    write!(stdout, "\x1b[0m\x1b[39m\x1b[49m")?;
+    if !stdout.is_terminal() {
+        return Ok(());
+    }
+    // Also show the cursor, in case it was hidden for `--animate`
+    write!(stdout, "\x1b[0m\x1b[39m\x1b[49m\x1b[?25h")?;
     stdout.flush()?;
     Ok(())
 }
@@ -0,0 +1,271 @@
+//! Conversions from rainbow coloring into TUI library types.
+//!
+//! Both integrations are built on [`Config::spans`], so a TUI shows the same
+//! colors the CLI prints for the same text. Unlike the spans, the text is
+//! given as a terminal would show it, without escape sequences or stray
+//! `\r`s and with tabs expanded to spaces colored cell by cell, as a TUI lays
+//! out and escapes the text itself.
+
+use std::borrow::Cow;
+
+use crate::color::Color;
+use crate::config::{Config, Layer};
+use crate::spans::Spans;
+
+/// `run` as a terminal shows it: escape sequences dropped, and any `\r`
+/// that is not part of a line ending, which a TUI would show as a character
+fn displayed(run: &str) -> Cow<'_, str> {
+    if !run.contains(['\x1b', '\r']) {
+        return Cow::Borrowed(run);
+    }
+    let bytes = run.as_bytes();
+    let mut shown = String::with_capacity(run.len());
+    // End of the escape sequence being skipped
+    let mut skip_to = 0;
+    for (i, c) in run.char_indices() {
+        if i < skip_to {
+            continue;
+        }
+        match c {
+            '\x1b' => skip_to = i + Spans::escape_len(bytes, i),
+            '\r' if bytes.get(i + 1) != Some(&b'\n') => {}
+            c => shown.push(c),
+        }
+    }
+    Cow::Owned(shown)
+}
+
+/// The spaces the tab just yielded by `spans` expands to, in runs of the
+/// color of each cell, as the CLI colors them
+fn tab_cells(spans: &Spans<'_>) -> Vec<(Color, Cow<'static, str>)> {
+    let mut cells: Vec<(Color, String)> = Vec::new();
+    for color in spans.tab_colors() {
+        match cells.last_mut() {
+            Some((last, run)) if *last == color => run.push(' '),
+            _ => cells.push((color, String::from(" "))),
+        }
+    }
+    cells
+        .into_iter()
+        .map(|(color, run)| (color, Cow::Owned(run)))
+        .collect()
+}
+
+/// Strip a trailing `\n` or `\r\n` from a span
+#[cfg(feature = "ratatui")]
+fn strip_line_ending(text: &str) -> (&str, bool) {
+    if let Some(text) = text.strip_suffix('\n') {
+        (text.strip_suffix('\r').unwrap_or(text), true)
+    } else {
+        (text, false)
+    }
+}
+
+#[cfg(feature = "ratatui")]
+impl From<Color> for ratatui::style::Color {
+    fn from(Color(r, g, b): Color) -> Self {
+        Self::Rgb(r, g, b)
+    }
+}
+
+#[cfg(feature = "crossterm")]
+impl From<Color> for crossterm::style::Color {
+    fn from(Color(r, g, b): Color) -> Self {
+        Self::Rgb { r, g, b }
+    }
+}
+
+#[cfg(feature = "ratatui")]
+impl Config {
+    /// Style a run of `color` on the configured [`Layer`]
+    fn ratatui_style(&self, color: Color) -> ratatui::style::Style {
+        let style = ratatui::style::Style::new();
+        match self.layer {
+            Layer::Foreground => style.fg(color.into()),
+            Layer::Background => style.bg(color.into()),
+        }
+    }
+
+    /// The runs of `text` as shown, each with whether it ends its line
+    fn ratatui_runs<'a>(&self, text: &'a str) -> Vec<(Color, Cow<'a, str>, bool)> {
+        let mut runs = Vec::new();
+        let mut spans = self.spans(text).split_tabs();
+        while let Some((color, run)) = spans.next() {
+            if run == "\t" {
+                runs.extend(
+                    tab_cells(&spans)
+                        .into_iter()
+                        .map(|(color, cells)| (color, cells, false)),
+                );
+                continue;
+            }
+            let (run, ends_line) = strip_line_ending(run);
+            runs.push((color, displayed(run), ends_line));
+        }
+        runs
+    }
+
+    /// Color a single line of text as a ratatui `Line`.
+    ///
+    /// Any line ending in `text` is dropped; use
+    /// [`Config::to_ratatui_text`] for multi-line text.
+    #[must_use]
+    pub fn to_ratatui_line<'a>(&self, text: &'a str) -> ratatui::text::Line<'a> {
+        use ratatui::text::{Line, Span};
+
+        let spans: Vec<Span<'a>> = self
+            .ratatui_runs(text)
+            .into_iter()
+            .filter(|(_, run, _)| !run.is_empty())
+            .map(|(color, run, _)| Span::styled(run, self.ratatui_style(color)))
+            .collect();
+        Line::from(spans)
+    }
+
+    /// Color multi-line text as a ratatui `Text`, one `Line` per input line
+    #[must_use]
+    pub fn to_ratatui_text<'a>(&self, text: &'a str) -> ratatui::text::Text<'a> {
+        use ratatui::text::{Line, Span, Text};
+
+        let mut lines = Vec::new();
+        let mut current = Vec::new();
+        for (color, run, ends_line) in self.ratatui_runs(text) {
+            if !run.is_empty() {
+                current.push(Span::styled(run, self.ratatui_style(color)));
+            }
+            if ends_line {
+                lines.push(Line::from(std::mem::take(&mut current)));
+            }
+        }
+        if !current.is_empty() {
+            lines.push(Line::from(current));
+        }
+        Text::from(lines)
+    }
+}
+
+#[cfg(feature = "crossterm")]
+impl Config {
+    /// Color text as crossterm styled content, one item per color run.
+    ///
+    /// Line endings are kept, so the items can be printed in sequence with
+    /// `crossterm::style::Print` or `{}` formatting.
+    #[must_use]
+    pub fn to_crossterm_styled<'a>(
+        &self,
+        text: &'a str,
+    ) -> Vec<crossterm::style::StyledContent<Cow<'a, str>>> {
+        use crossterm::style::{ContentStyle, StyledContent};
+
+        let styled = |color: Color, shown| {
+            let mut style = ContentStyle::default();
+            match self.layer {
+                Layer::Foreground => style.foreground_color = Some(color.into()),
+                Layer::Background => style.background_color = Some(color.into()),
+            }
+            StyledContent::new(style, shown)
+        };
+        let mut items = Vec::new();
+        let mut spans = self.spans(text).split_tabs();
+        while let Some((color, run)) = spans.next() {
+            if run == "\t" {
+                items.extend(
+                    tab_cells(&spans)
+                        .into_iter()
+                        .map(|(color, cells)| styled(color, cells)),
+                );
+            } else {
+                items.push(styled(color, displayed(run)));
+            }
+        }
+        items
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "crossterm")]
+    use crate::config::TabStops;
+
+    #[cfg(feature = "ratatui")]
+    #[test]
+    fn ratatui_text_matches_spans() {
+        let config = Config::try_new(0.3, 2.0, false).unwrap();
+        let input = "first line\r\nsecond\n\nlast";
+        let text = config.to_ratatui_text(input);
+
+        assert_eq!(text.lines.len(), 4);
+        let plain: Vec<String> = text.lines.iter().map(ToString::to_string).collect();
+        assert_eq!(plain, ["first line", "second", "", "last"]);
+
+        let (color, _) = config.spans(input).next().unwrap();
+        assert_eq!(text.lines[0].spans[0].style.fg, Some(color.into()));
+    }
+
+    #[cfg(feature = "ratatui")]
+    #[test]
+    fn ratatui_text_is_shown_as_a_terminal_would() {
+        let config = Config::try_new(0.3, 2.0, false).unwrap();
+        let text = config.to_ratatui_text("a\tb\x1b[1mold\x1b[0m\n\tc");
+        let plain: Vec<String> = text.lines.iter().map(ToString::to_string).collect();
+        assert_eq!(plain, ["a       bold", "        c"]);
+        let line = config.to_ratatui_line("ab\tc\x1b[0m");
+        assert_eq!(line.to_string(), "ab      c");
+    }
+
+    #[cfg(feature = "ratatui")]
+    #[test]
+    fn ratatui_tabs_are_colored_cell_by_cell() {
+        // Colors change from one column to the next
+        let config = Config::try_new(1.0, 0.5, false).unwrap();
+        let cells = |text| {
+            let line = config.to_ratatui_line(text);
+            line.spans
+                .iter()
+                .flat_map(|span| span.content.chars().map(|c| (c, span.style)))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(cells("ab\tc\td"), cells("ab      c       d"));
+    }
+
+    #[cfg(feature = "ratatui")]
+    #[test]
+    fn ratatui_text_drops_stray_carriage_returns() {
+        let config = Config::try_new(0.3, 2.0, false).unwrap();
+        let text = config.to_ratatui_text("one\rtwo\r\r\nthree\r");
+        let plain: Vec<String> = text.lines.iter().map(ToString::to_string).collect();
+        assert_eq!(plain, ["onetwo", "three"]);
+        assert!(
+            text.lines
+                .iter()
+                .flat_map(|line| &line.spans)
+                .all(|span| !span.content.contains('\r'))
+        );
+    }
+
+    #[cfg(feature = "crossterm")]
+    #[test]
+    fn crossterm_styled_is_shown_as_a_terminal_would() {
+        let mut config = Config::try_new(0.3, 2.0, false).unwrap();
+        config.tab_stops = TabStops::Every(4);
+        let styled = config.to_crossterm_styled("\x1b[31mab\tc\r\n\td\re");
+        let shown: String = styled.iter().map(|item| item.content().as_ref()).collect();
+        assert_eq!(shown, "ab  c\r\n    de");
+    }
+
+    #[cfg(feature = "crossterm")]
+    #[test]
+    fn crossterm_styled_matches_spans() {
+        let config = Config::try_new(0.3, 2.0, false).unwrap();
+        let input = "rainbow\ntitle";
+        let styled = config.to_crossterm_styled(input);
+        let spans: Vec<_> = config.spans(input).collect();
+
+        assert_eq!(styled.len(), spans.len());
+        for (item, (color, run)) in styled.iter().zip(spans) {
+            assert_eq!(item.content(), run);
+            assert_eq!(item.style().foreground_color, Some(color.into()));
+        }
+    }
+}
@@ -0,0 +1,454 @@
+use std::io::{self, Write};
+
+use crate::ansi::MAX_ANSI_SEQUENCE_LENGTH;
+use crate::color::ColorMode;
+use crate::config::{CarriageReturns, Config, Layer, LineEndings, Tabs};
+use crate::processor::{DEFAULT_BACKGROUND, get_ansi_256, set_background};
+use crate::rainbow::RainbowLookup;
+
+/// A `std::io::Write` adaptor that rainbow-colors everything written to it.
+///
+/// Bytes are colored as they arrive, using the same per-line phase as
+/// `process_input_with_color_mode`, so writing a whole input through a
+/// `RainbowWriter` produces the same output as processing it from a reader,
+/// except at the end: the processor ends a final line that has no line
+/// ending with `\n` unless line endings are preserved, while the writer
+/// leaves what was written as it is, to color fragments of text.
+///
+/// Lines, UTF-8 sequences and ANSI escape sequences may be split across any
+/// number of `write` calls; the state needed to continue them is carried
+/// over internally.
+///
+/// Call [`RainbowWriter::finish`] when done to emit the terminal reset
+/// sequence and get the inner writer back.
+pub struct RainbowWriter<W: Write> {
+    inner: W,
+    config: Config,
+    color_mode: ColorMode,
+    lookup: RainbowLookup,
+    /// Output staging buffer, reused across `write` calls
+    buf: Vec<u8>,
+    lines_read: usize,
+    /// Phase at the start of the current line
+    start_phase: u64,
+    phase: u64,
+    phase_inc: u64,
+    last_color_idx: Option<usize>,
+    /// Columns written on the current line, for tab stops
+    column: usize,
+    /// Number of bytes consumed after ESC while inside an escape sequence
+    escape_len: Option<usize>,
+    /// Column parameter of an escape that may move the cursor (`ESC [ n G`)
+    escape_column: Option<u32>,
+    /// A `\r` that may turn out to be part of a `\r\n` line ending
+    pending_cr: bool,
+    /// Whether the inner writer should be flushed after this `write`
+    needs_flush: bool,
+}
+
+impl<W: Write> RainbowWriter<W> {
+    /// Create a writer using the color mode detected for stdout
+    #[must_use]
+    pub fn new(inner: W, config: &Config) -> Self {
+        let color_mode = config.color_mode_for(&io::stdout());
+        Self::with_color_mode(inner, config, color_mode)
+    }
+
+    /// Create a writer with an explicit color mode
+    #[must_use]
+    pub fn with_color_mode(inner: W, config: &Config, color_mode: ColorMode) -> Self {
+        let lookup = RainbowLookup::new(config.frequency);
+        let (phase, phase_inc) =
+            lookup.fixedpoint_phase(config.line_start_pos(0), config.column_step());
+        Self {
+            inner,
+            config: config.clone(),
+            color_mode,
+            lookup,
+            buf: Vec::with_capacity(8192),
+            lines_read: 0,
+            start_phase: phase,
+            phase,
+            phase_inc,
+            last_color_idx: None,
+            column: 0,
+            escape_len: None,
+            escape_column: None,
+            pending_cr: false,
+            needs_flush: false,
+        }
+    }
+
+    /// Write any pending state and the terminal reset sequence, flush, and
+    /// return the inner writer. No `\n` is added after a final line without
+    /// one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to or flushing the inner writer fails
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.color_mode != ColorMode::NoColor {
+            if self.pending_cr {
+                self.pending_cr = false;
+                self.push_carriage_return();
+            }
+            self.buf.extend_from_slice(b"\x1b[0m\x1b[39m\x1b[49m");
+            self.inner.write_all(&self.buf)?;
+            self.buf.clear();
+        }
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    /// Feed one input byte, resolving a pending `\r` first
+    #[inline]
+    fn feed(&mut self, b: u8) {
+        if self.pending_cr {
+            self.pending_cr = false;
+            if b == b'\n' {
+                // CRLF: the `\r` is dropped unless line endings are preserved,
+                // and never colored
+                self.end_background();
+                if self.config.line_endings == LineEndings::Preserve {
+                    self.buf.push(b'\r');
+                }
+                self.buf.push(b'\n');
+                self.start_next_line();
+                return;
+            }
+            self.push_carriage_return();
+        }
+        if b == b'\r' {
+            self.pending_cr = true;
+        } else {
+            self.push_byte(b);
+        }
+    }
+
+    /// Push a `\r` that does not end a line
+    #[inline]
+    fn push_carriage_return(&mut self) {
+        if self.config.carriage_returns == CarriageReturns::Rewind {
+            self.buf.push(b'\r');
+            self.rewind();
+        } else {
+            self.push_byte(b'\r');
+        }
+    }
+
+    /// Restart the line's rainbow after the cursor went back to its start
+    fn rewind(&mut self) {
+        self.phase = self.start_phase;
+        self.last_color_idx = None;
+        self.column = 0;
+        self.needs_flush = true;
+    }
+
+    /// Color one byte into the staging buffer
+    #[inline]
+    fn push_byte(&mut self, b: u8) {
+        if b == b'\n' {
+            self.end_background();
+            self.buf.push(b'\n');
+            self.start_next_line();
+            return;
+        }
+
+        if let Some(len) = self.escape_len {
+            self.buf.push(b);
+            let len = len + 1;
+            self.escape_column = match (self.escape_column, b) {
+                (None, b'[') if len == 1 => Some(0),
+                (Some(column), b'0'..=b'9') => Some(
+                    column
+                        .saturating_mul(10)
+                        .saturating_add(u32::from(b - b'0')),
+                ),
+                (Some(column), b'G')
+                    if column <= 1 && self.config.carriage_returns == CarriageReturns::Rewind =>
+                {
+                    self.rewind();
+                    None
+                }
+                _ => None,
+            };
+            // ANSI sequences end on ASCII alphabetic characters (A–Z, a–z)
+            self.escape_len = if b.is_ascii_alphabetic() || len >= MAX_ANSI_SEQUENCE_LENGTH {
+                None
+            } else {
+                Some(len)
+            };
+            return;
+        }
+
+        match b {
+            0x1b => {
+                self.buf.push(b);
+                self.escape_len = Some(0);
+                self.escape_column = None;
+                self.last_color_idx = None;
+            }
+            b'\t' => {
+                let width = self.config.tab_stops.width_at(self.column);
+                if self.config.tabs == Tabs::Preserve {
+                    self.buf.push(b'\t');
+                    self.phase = self
+                        .phase
+                        .wrapping_add(self.phase_inc.wrapping_mul(width as u64));
+                    self.column += width;
+                } else {
+                    for _ in 0..width {
+                        self.push_colored(b' ');
+                    }
+                }
+            }
+            // Continuation bytes never get a color sequence in front of them
+            0x80..0xC0 => self.buf.push(b),
+            _ => self.push_colored(b),
+        }
+    }
+
+    /// Emit a color sequence if needed, push a codepoint-start byte and
+    /// advance the phase
+    #[inline]
+    fn push_colored(&mut self, b: u8) {
+        let color_idx = self.lookup.color_index_from_phase(self.phase);
+        if self.last_color_idx != Some(color_idx) {
+            let start = self.buf.len();
+            match self.color_mode {
+                ColorMode::TrueColor => self
+                    .buf
+                    .extend_from_slice(self.lookup.get_truecolor_ansi(color_idx)),
+                ColorMode::Color256 => {
+                    let code = self.lookup.get_256_code(color_idx);
+                    self.buf.extend_from_slice(get_ansi_256(code));
+                }
+                ColorMode::NoColor => {}
+            }
+            if self.config.layer == Layer::Background && self.buf.len() > start {
+                set_background(&mut self.buf, start);
+            }
+            self.last_color_idx = Some(color_idx);
+        }
+        self.buf.push(b);
+        self.phase = self.phase.wrapping_add(self.phase_inc);
+        self.column += 1;
+    }
+
+    /// Reset a colored background before a line ending, like the processor
+    fn end_background(&mut self) {
+        if self.config.layer == Layer::Background && self.color_mode != ColorMode::NoColor {
+            self.buf.extend_from_slice(DEFAULT_BACKGROUND);
+        }
+    }
+
+    fn start_next_line(&mut self) {
+        self.lines_read += 1;
+        let start_pos = self.config.line_start_pos(self.lines_read);
+        let (phase, _) = self
+            .lookup
+            .fixedpoint_phase(start_pos, self.config.column_step());
+        self.start_phase = phase;
+        self.phase = phase;
+        self.last_color_idx = None;
+        self.column = 0;
+        self.escape_len = None;
+    }
+}
+
+impl<W: Write> Write for RainbowWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if self.color_mode == ColorMode::NoColor {
+            return self.inner.write(data);
+        }
+        for &b in data {
+            self.feed(b);
+        }
+        if self.pending_cr && self.config.carriage_returns == CarriageReturns::Rewind {
+            // Show what was drawn so far; the held-back `\r` only moves the
+            // cursor, which can wait for the next redraw
+            self.needs_flush = true;
+        }
+        let result = self.inner.write_all(&self.buf);
+        self.buf.clear();
+        result?;
+        if std::mem::take(&mut self.needs_flush) {
+            self.inner.flush()?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Direction, TabStops};
+    use crate::processor::process_input_with_color_mode;
+    use std::io::{BufReader, Cursor};
+
+    fn process_reference(input: &[u8], config: &Config, color_mode: ColorMode) -> Vec<u8> {
+        let reader = BufReader::new(Cursor::new(input));
+        let mut output = Vec::new();
+        process_input_with_color_mode(reader, &mut output, config, color_mode).unwrap();
+        output
+    }
+
+    fn write_in_chunks(
+        input: &[u8],
+        chunk: usize,
+        config: &Config,
+        color_mode: ColorMode,
+    ) -> Vec<u8> {
+        let mut writer = RainbowWriter::with_color_mode(Vec::new(), config, color_mode);
+        for part in input.chunks(chunk) {
+            writer.write_all(part).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    const MIXED_INPUT: &str = "Hello, world!\r\n\
+        caf\u{00e9} \u{4e16}\u{754c} \u{1F308}\n\
+        col1\tcol2\t\x1b[1mbold\x1b[0m\n\
+        \n\
+        trailing\r\n";
+
+    #[test]
+    fn matches_processor_truecolor() {
+        let config = Config::try_new(0.04, 4.0, true).unwrap();
+        let expected = process_reference(MIXED_INPUT.as_bytes(), &config, ColorMode::TrueColor);
+        for chunk in [1, 2, 3, 7, MIXED_INPUT.len()] {
+            let output =
+                write_in_chunks(MIXED_INPUT.as_bytes(), chunk, &config, ColorMode::TrueColor);
+            assert_eq!(output, expected, "chunk size {chunk}");
+        }
+    }
+
+    #[test]
+    fn matches_processor_256color() {
+        let config = Config::try_new(0.3, 2.0, true).unwrap();
+        let expected = process_reference(MIXED_INPUT.as_bytes(), &config, ColorMode::Color256);
+        for chunk in [1, 5, MIXED_INPUT.len()] {
+            let output =
+                write_in_chunks(MIXED_INPUT.as_bytes(), chunk, &config, ColorMode::Color256);
+            assert_eq!(output, expected, "chunk size {chunk}");
+        }
+    }
+
+    #[test]
+    fn matches_processor_preserving_line_endings() {
+        let mut config = Config::try_new(0.04, 4.0, true).unwrap();
+        config.line_endings = LineEndings::Preserve;
+        let expected = process_reference(MIXED_INPUT.as_bytes(), &config, ColorMode::TrueColor);
+        for chunk in [1, 2, 3, MIXED_INPUT.len()] {
+            let output =
+                write_in_chunks(MIXED_INPUT.as_bytes(), chunk, &config, ColorMode::TrueColor);
+            assert_eq!(output, expected, "chunk size {chunk}");
+        }
+    }
+
+    #[test]
+    fn final_line_is_left_without_newline() {
+        let mut config = Config::try_new(0.04, 4.0, true).unwrap();
+        let input = b"one\ntwo";
+        let expected = process_reference(input, &config, ColorMode::TrueColor);
+        let output = write_in_chunks(input, 3, &config, ColorMode::TrueColor);
+        let reset = b"\x1b[0m\x1b[39m\x1b[49m";
+        // The processor adds the newline before the reset sequence
+        let newline = expected.len() - reset.len() - 1;
+        assert_eq!(expected[newline], b'\n');
+        assert_eq!(output, [&expected[..newline], reset].concat());
+
+        config.line_endings = LineEndings::Preserve;
+        let expected = process_reference(input, &config, ColorMode::TrueColor);
+        assert_eq!(
+            write_in_chunks(input, 3, &config, ColorMode::TrueColor),
+            expected
+        );
+    }
+
+    #[test]
+    fn matches_processor_rewinding_carriage_returns() {
+        let mut config = Config::try_new(0.04, 4.0, true).unwrap();
+        config.carriage_returns = CarriageReturns::Rewind;
+        let input = "progress  10%\rprogress  50%\x1b[1Gprogress 100%\r\nat\x1b[Gdone\n";
+        let expected = process_reference(input.as_bytes(), &config, ColorMode::TrueColor);
+        for chunk in [1, 2, 3, input.len()] {
+            let output = write_in_chunks(input.as_bytes(), chunk, &config, ColorMode::TrueColor);
+            assert_eq!(output, expected, "chunk size {chunk}");
+        }
+    }
+
+    #[test]
+    fn matches_processor_with_tab_stops() {
+        let mut config = Config::try_new(0.3, 1.0, true).unwrap();
+        config.tab_stops = TabStops::List(vec![3, 6, 20]);
+        for tabs in [Tabs::Expand, Tabs::Preserve] {
+            config.tabs = tabs;
+            let expected = process_reference(MIXED_INPUT.as_bytes(), &config, ColorMode::TrueColor);
+            for chunk in [1, 4, MIXED_INPUT.len()] {
+                let output =
+                    write_in_chunks(MIXED_INPUT.as_bytes(), chunk, &config, ColorMode::TrueColor);
+                assert_eq!(output, expected, "{tabs:?}, chunk size {chunk}");
+            }
+        }
+    }
+
+    #[test]
+    fn matches_processor_coloring_background() {
+        let mut config = Config::try_new(0.3, 1.0, true).unwrap();
+        config.layer = Layer::Background;
+        for color_mode in [ColorMode::TrueColor, ColorMode::Color256] {
+            let expected = process_reference(MIXED_INPUT.as_bytes(), &config, color_mode);
+            for chunk in [1, 4, MIXED_INPUT.len()] {
+                let output = write_in_chunks(MIXED_INPUT.as_bytes(), chunk, &config, color_mode);
+                assert_eq!(output, expected, "{color_mode:?}, chunk size {chunk}");
+            }
+        }
+    }
+
+    #[test]
+    fn matches_processor_coloring_in_every_direction() {
+        let mut config = Config::try_new(0.3, 1.5, true).unwrap();
+        for direction in [
+            Direction::Horizontal,
+            Direction::Vertical,
+            Direction::Angle(135.0),
+            Direction::Angle(-20.0),
+        ] {
+            config.direction = direction;
+            let expected = process_reference(MIXED_INPUT.as_bytes(), &config, ColorMode::TrueColor);
+            let output = write_in_chunks(MIXED_INPUT.as_bytes(), 3, &config, ColorMode::TrueColor);
+            assert_eq!(output, expected, "{direction:?}");
+        }
+    }
+
+    #[test]
+    fn no_color_passes_bytes_through() {
+        let config = Config::try_new(0.04, 4.0, false).unwrap();
+        let output = write_in_chunks(MIXED_INPUT.as_bytes(), 3, &config, ColorMode::NoColor);
+        assert_eq!(output, MIXED_INPUT.as_bytes());
+    }
+
+    #[test]
+    fn matches_compile_time_macros() {
+        let mut config = Config::try_new(0.04, 4.0, true).unwrap();
+        config.set_offset(0.0);
+        let input = "Hello,\tworld!\r\ncaf\u{00e9} \x1b[1m\u{1F308}\x1b[0m\nlast";
+
+        let output = write_in_chunks(input.as_bytes(), input.len(), &config, ColorMode::TrueColor);
+        let expected = lolcat_ultra_macros::rainbow!(
+            "Hello,\tworld!\r\ncaf\u{00e9} \x1b[1m\u{1F308}\x1b[0m\nlast"
+        );
+        assert_eq!(output, expected.as_bytes());
+
+        let output = write_in_chunks(input.as_bytes(), input.len(), &config, ColorMode::Color256);
+        let expected = lolcat_ultra_macros::rainbow_256!(
+            "Hello,\tworld!\r\ncaf\u{00e9} \x1b[1m\u{1F308}\x1b[0m\nlast"
+        );
+        assert_eq!(output, expected.as_bytes());
+    }
+}
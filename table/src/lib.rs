@@ -0,0 +1,93 @@
+//! The rainbow color table of lolcat-ultra.
+//!
+//! Generated by lolcat-ultra's build script and by its compile-time macros
+//! alike, so the macros produce exactly the colors the library prints at
+//! runtime.
+
+const AMPLITUDE: f64 = 127.0;
+const OFFSET: f64 = 128.0;
+const C: f64 = -0.5; // cos(2π/3)
+const S: f64 = 0.866_025_403_784_438_6_f64; // sin(2π/3) = √3/2
+/// Number of colors in one turn of the rainbow
+pub const TABLE_SIZE: usize = 2048;
+/// Mask taking an index into the table
+pub const MASK: usize = TABLE_SIZE - 1;
+
+/// An RGB color
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color(pub u8, pub u8, pub u8);
+
+/// Branch-free saturating f64 to u8 converter
+fn fast_f64_to_u8_sat(x: f64) -> u8 {
+    let y = x.clamp(0.0, 255.0) + 0.5;
+    y as u8
+}
+
+/// Convert RGB to 256-color palette
+#[must_use]
+pub const fn rgb_to_256(r: u8, g: u8, b: u8) -> u8 {
+    if r == g && g == b {
+        if r < 8 {
+            16
+        } else if r > 248 {
+            231
+        } else {
+            232 + (((((r as u16) - 8) * 25) >> 8) as u8)
+        }
+    } else {
+        // Approximate division by 51 with bit shift
+        let r6 = ((r as u16) * 5) >> 8;
+        let g6 = ((g as u16) * 5) >> 8;
+        let b6 = ((b as u16) * 5) >> 8;
+        (16 + 36 * r6 + 6 * g6 + b6) as u8
+    }
+}
+
+/// Build frequency-agnostic rainbow color table using trig recurrence
+#[must_use]
+pub fn build_table() -> [Color; TABLE_SIZE] {
+    let mut arr = [Color(0, 0, 0); TABLE_SIZE];
+
+    let delta = std::f64::consts::TAU / (TABLE_SIZE as f64);
+    let (mut sx, mut cx) = (0.0_f64).sin_cos();
+    let (sd, cd) = delta.sin_cos();
+
+    for color in &mut arr {
+        let r = fast_f64_to_u8_sat(sx.mul_add(AMPLITUDE, OFFSET));
+        let g = fast_f64_to_u8_sat((sx * C + cx * S).mul_add(AMPLITUDE, OFFSET));
+        let b = fast_f64_to_u8_sat((sx * C - cx * S).mul_add(AMPLITUDE, OFFSET));
+        *color = Color(r, g, b);
+
+        let ns = sx.mul_add(cd, cx * sd);
+        let nc = cx.mul_add(cd, -sx * sd);
+        sx = ns;
+        cx = nc;
+    }
+
+    arr
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channels_are_a_third_of_a_turn_apart() {
+        let table = build_table();
+        // sin 0 = 0 and cos 0 = 1: red in the middle, green and blue apart
+        assert_eq!(table[0], Color(128, 238, 18));
+        // The three sines add up to zero, so the channels to three midpoints
+        for &Color(r, g, b) in &table {
+            let sum = u16::from(r) + u16::from(g) + u16::from(b);
+            assert!((383..=386).contains(&sum), "{sum}");
+        }
+    }
+
+    #[test]
+    fn grays_and_colors_map_to_the_palette() {
+        assert_eq!(rgb_to_256(0, 0, 0), 16);
+        assert_eq!(rgb_to_256(255, 255, 255), 231);
+        assert_eq!(rgb_to_256(128, 128, 128), 232 + 11);
+        assert_eq!(rgb_to_256(255, 0, 0), 16 + 36 * 4);
+    }
+}
@@ -0,0 +1,129 @@
+//! How the binary reads several inputs one after another
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+/// Path of a file named for the test using it
+fn temp_path(name: &str) -> String {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    path.to_str().unwrap().to_string()
+}
+
+/// Write `contents` to a file named for the test using it
+fn file(name: &str, contents: &str) -> String {
+    let path = temp_path(name);
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+/// Run the binary on `args` with `stdin` as its input. Not a terminal, so
+/// the output is only colored when forced.
+fn run(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lolcat-ultra"))
+        .arg("--no-config")
+        .args(args)
+        .env_remove("LOLCAT_OPTS")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn files_are_read_in_order() {
+    let one = file("order-one", "one\n");
+    let two = file("order-two", "two\nthree\n");
+    let output = run(&[&one, &two], "");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"one\ntwo\nthree\n");
+}
+
+#[test]
+fn stdin_is_read_in_place_of_a_dash() {
+    let one = file("dash-one", "one\n");
+    let two = file("dash-two", "two\n");
+    let output = run(&[&one, "-", &two], "middle\n");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"one\nmiddle\ntwo\n");
+}
+
+#[test]
+fn line_numbers_carry_over_between_files() {
+    let one = file("numbers-one", "one\n");
+    let two = file("numbers-two", "two\n");
+    let output = run(&["-n", &one, &two], "");
+    assert_eq!(output.stdout, b"     1  one\n     2  two\n");
+}
+
+#[test]
+fn missing_files_are_reported_and_skipped() {
+    let one = file("missing-one", "one\n");
+    let missing = temp_path("missing-none");
+    let two = file("missing-two", "two\n");
+    let output = run(&[&one, &missing, &two], "");
+    assert_eq!(output.stdout, b"one\ntwo\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(&missing), "{stderr}");
+    assert_eq!(output.status.code(), Some(66));
+}
+
+#[test]
+fn unreadable_inputs_fail_with_an_io_error() {
+    let one = file("unreadable-one", "one\n");
+    let directory = env!("CARGO_TARGET_TMPDIR");
+    let output = run(&[directory, &one], "");
+    assert_eq!(output.stdout, b"one\n");
+    assert_eq!(output.status.code(), Some(74));
+
+    // A later input that cannot be opened does not lower it
+    let missing = temp_path("unreadable-none");
+    let output = run(&[directory, &missing], "");
+    assert_eq!(output.status.code(), Some(74));
+}
+
+#[test]
+fn restarting_per_file_repeats_the_rainbow() {
+    let one = file("restart-one", "same text\n");
+    let two = file("restart-two", "same text\n");
+    let colored = |restart: bool| {
+        let mut args = vec!["-F", &one, &two];
+        if restart {
+            args.insert(0, "--restart-per-file");
+        }
+        let output = run(&args, "");
+        assert!(output.status.success());
+        // Each stream ends with the reset sequence
+        let text = String::from_utf8(output.stdout)
+            .unwrap()
+            .replace("\x1b[0m\x1b[39m\x1b[49m", "");
+        let lines: Vec<String> = text.lines().map(str::to_string).collect();
+        assert_eq!(lines.len(), 2, "{text:?}");
+        lines
+    };
+
+    let lines = colored(true);
+    assert_eq!(lines[0], lines[1]);
+    let lines = colored(false);
+    assert_ne!(lines[0], lines[1]);
+}
+
+#[test]
+fn only_stdin_line_endings_are_normalized() {
+    let crlf = file("endings-crlf", "file\r\n");
+    let output = run(&[&crlf, "-", &crlf], "stdin\r\nlast");
+    assert_eq!(output.stdout, b"file\r\nstdin\nlast\nfile\r\n");
+
+    let output = run(&["--line-endings", "preserve", &crlf, "-"], "stdin\r\n");
+    assert_eq!(output.stdout, b"file\r\nstdin\r\n");
+    let output = run(&["--line-endings", "normalize", &crlf, "-"], "stdin\r\n");
+    assert_eq!(output.stdout, b"file\nstdin\n");
+}
@@ -0,0 +1,56 @@
+//! How library processing stops on signals, only while asked to
+#![cfg(unix)]
+
+use std::os::unix::process::ExitStatusExt;
+use std::process::Command;
+
+use lolcat_ultra::{ColorMode, Config, ProcessError};
+use signal_hook::consts::SIGINT;
+
+fn process(input: &[u8]) -> Result<usize, ProcessError> {
+    let config = Config::try_new(0.1, 3.0, true).unwrap();
+    lolcat_ultra::process_input_with_color_mode(input, Vec::new(), &config, ColorMode::TrueColor)
+}
+
+#[test]
+fn signals_stop_processing_while_the_guard_lives() {
+    // Only the panic hook; processing is not stopped by anything
+    lolcat_ultra::setup_terminal_cleanup();
+    assert_eq!(process(b"one\ntwo\n").unwrap(), 2);
+
+    let guard = lolcat_ultra::handle_interrupts().unwrap();
+    signal_hook::low_level::raise(SIGINT).unwrap();
+    assert!(matches!(
+        process(b"one\ntwo\n"),
+        Err(ProcessError::Interrupted(SIGINT))
+    ));
+    // Still interrupted until the caller gives up handling signals
+    assert!(process(b"one\n").is_err());
+
+    drop(guard);
+    assert_eq!(process(b"one\ntwo\n").unwrap(), 2);
+
+    // Handled again, without the earlier signal
+    let _guard = lolcat_ultra::handle_interrupts().unwrap();
+    assert_eq!(process(b"one\n").unwrap(), 1);
+}
+
+#[test]
+fn signals_end_the_process_once_the_guard_is_dropped() {
+    // Run in a child, which SIGINT ends
+    if std::env::var_os("LOLCAT_ULTRA_RAISE").is_some() {
+        drop(lolcat_ultra::handle_interrupts().unwrap());
+        signal_hook::low_level::raise(SIGINT).unwrap();
+        std::process::exit(0);
+    }
+    let status = Command::new(std::env::current_exe().unwrap())
+        .args([
+            "--exact",
+            "signals_end_the_process_once_the_guard_is_dropped",
+        ])
+        .env("LOLCAT_ULTRA_RAISE", "1")
+        .output()
+        .unwrap()
+        .status;
+    assert_eq!(status.signal(), Some(SIGINT));
+}
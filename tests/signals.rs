@@ -0,0 +1,64 @@
+//! How the binary ends when interrupted by a signal
+#![cfg(unix)]
+
+use std::io::{Read, Write};
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+const SIGINT: i32 = 2;
+
+fn spawn() -> Child {
+    Command::new(env!("CARGO_BIN_EXE_lolcat-ultra"))
+        .arg("-F")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap()
+}
+
+fn interrupt(child: &Child) {
+    let status = Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn interrupted_while_reading_ends_by_the_signal() {
+    let mut child = spawn();
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(b"hello\n").unwrap();
+    // Give the signal handlers time to be installed
+    thread::sleep(Duration::from_millis(300));
+
+    // Blocked reading the rest of the input, so only the grace period ends it
+    interrupt(&child);
+    let output = child.wait_with_output().unwrap();
+    drop(stdin);
+    assert_eq!(output.status.signal(), Some(SIGINT));
+    // Not a terminal, so no reset sequence showing a cursor
+    assert!(!output.stdout.windows(6).any(|w| w == b"\x1b[?25h"));
+}
+
+#[test]
+fn interrupted_while_processing_stops_and_resets_colors() {
+    let mut child = spawn();
+    let mut stdin = child.stdin.take().unwrap();
+    let writer = thread::spawn(move || {
+        let line = [b"x".repeat(70).as_slice(), b"\n"].concat();
+        while stdin.write_all(&line).is_ok() {}
+    });
+    let mut stdout = child.stdout.take().unwrap();
+    let mut output = vec![0; 64 * 1024];
+    stdout.read_exact(&mut output).unwrap();
+
+    interrupt(&child);
+    stdout.read_to_end(&mut output).unwrap();
+    let status = child.wait().unwrap();
+    writer.join().unwrap();
+    assert_eq!(status.signal(), Some(SIGINT));
+    assert!(output.ends_with(b"\x1b[0m\x1b[39m\x1b[49m"));
+}